@@ -0,0 +1,122 @@
+//! Times the search dropdown and the Products page list one keystroke at a
+//! time against a 50,000 product catalog.
+//!
+//! Run with `cargo bench --bench product_search`.
+
+use general_store_manager::{ProductFilter, Store};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const PRODUCTS: usize = 50_000;
+
+const BRANDS: [&str; 20] = [
+    "Amul",
+    "Britannia",
+    "Fortune",
+    "Tata",
+    "Aashirvaad",
+    "Parle",
+    "Haldiram",
+    "Nestle",
+    "Dabur",
+    "Patanjali",
+    "Mother Dairy",
+    "Saffola",
+    "Everest",
+    "MDH",
+    "Catch",
+    "Lijjat",
+    "Surf Excel",
+    "Colgate",
+    "Dettol",
+    "Maggi",
+];
+
+const ITEMS: [&str; 25] = [
+    "Butter",
+    "Cheese",
+    "Ghee",
+    "Milk",
+    "Paneer",
+    "Atta",
+    "Rice",
+    "Sugar",
+    "Salt",
+    "Tea",
+    "Coffee",
+    "Biscuits",
+    "Namkeen",
+    "Oil",
+    "Honey",
+    "Turmeric",
+    "Chilli Powder",
+    "Garam Masala",
+    "Papad",
+    "Detergent",
+    "Toothpaste",
+    "Soap",
+    "Noodles",
+    "Dal",
+    "Besan",
+];
+
+const VARIANTS: [&str; 10] = [
+    "Classic", "Gold", "Premium", "Lite", "Family", "Special", "Organic", "Fresh", "Select",
+    "Daily",
+];
+
+const PACKS: [&str; 10] = [
+    "50g", "100g", "200g", "500g", "1kg", "5kg", "200ml", "500ml", "1L", "5L",
+];
+
+/// What a shopkeeper might type, timed after every character.
+const QUERIES: [&str; 3] = ["amul gold butter", "britania", "garam masala 100g"];
+
+fn main() {
+    let mut store = Store::build(Path::new(":memory:")).unwrap();
+
+    let started = Instant::now();
+    for n in 0..PRODUCTS {
+        let brand = BRANDS[n % BRANDS.len()];
+        let item = ITEMS[(n / BRANDS.len()) % ITEMS.len()];
+        let variant = VARIANTS[(n / 7) % VARIANTS.len()];
+        let pack = PACKS[(n / 3) % PACKS.len()];
+        store
+            .add_product(&format!("{variant} {item} {n}"), brand, item, pack, None)
+            .unwrap();
+    }
+    println!("built {PRODUCTS} products in {:.1?}", started.elapsed());
+
+    for query in QUERIES {
+        println!("\n\"{query}\"");
+        println!("{:<20} {:>12} {:>12}", "typed", "dropdown", "page");
+        for end in query.char_indices().map(|(start, c)| start + c.len_utf8()) {
+            let typed = &query[..end];
+            let dropdown = time(|| {
+                store.get_best_product_results_for(typed).unwrap();
+            });
+            let page = time(|| {
+                store
+                    .search_products(&ProductFilter {
+                        text: typed.to_string(),
+                        ..Default::default()
+                    })
+                    .unwrap();
+            });
+            println!("{typed:<20} {dropdown:>12.2?} {page:>12.2?}");
+        }
+    }
+}
+
+/// The median of a few runs, so one slow run does not skew the table.
+fn time(mut search: impl FnMut()) -> Duration {
+    let mut runs: Vec<Duration> = (0..5)
+        .map(|_| {
+            let started = Instant::now();
+            search();
+            started.elapsed()
+        })
+        .collect();
+    runs.sort();
+    runs[runs.len() / 2]
+}
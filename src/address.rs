@@ -0,0 +1,395 @@
+use crate::{ensure_exists, required, Result, Store, StoreError};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+
+/// States and union territories as they should be written on an Indian address.
+pub const INDIAN_STATES: &[&str] = &[
+    "Andhra Pradesh",
+    "Arunachal Pradesh",
+    "Assam",
+    "Bihar",
+    "Chhattisgarh",
+    "Goa",
+    "Gujarat",
+    "Haryana",
+    "Himachal Pradesh",
+    "Jharkhand",
+    "Karnataka",
+    "Kerala",
+    "Madhya Pradesh",
+    "Maharashtra",
+    "Manipur",
+    "Meghalaya",
+    "Mizoram",
+    "Nagaland",
+    "Odisha",
+    "Punjab",
+    "Rajasthan",
+    "Sikkim",
+    "Tamil Nadu",
+    "Telangana",
+    "Tripura",
+    "Uttar Pradesh",
+    "Uttarakhand",
+    "West Bengal",
+    "Andaman and Nicobar Islands",
+    "Chandigarh",
+    "Dadra and Nagar Haveli and Daman and Diu",
+    "Delhi",
+    "Jammu and Kashmir",
+    "Ladakh",
+    "Lakshadweep",
+    "Puducherry",
+];
+
+/// One of a dealer's locations. `description` says what the place is to the
+/// dealer, e.g. "godown", "shop" or "home".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Address {
+    pub address_id: i64,
+    pub description: Option<String>,
+    pub house_num: Option<String>,
+    pub street_name: Option<String>,
+    pub locality_name: Option<String>,
+    pub city_name: String,
+    pub district_name: String,
+    pub pin_code: String,
+    pub state: String,
+    pub country: String,
+}
+
+impl Address {
+    /// Returns a trimmed copy with blank optional parts dropped, or the first
+    /// problem found. Indian addresses must have a valid PIN code and state.
+    pub fn normalized(&self) -> Result<Address> {
+        let optional = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+
+        let country = required("Country", &self.country)?;
+        let mut state = required("State", &self.state)?.to_string();
+        let mut pin_code = required("PIN code", &self.pin_code)?.to_string();
+
+        if country.eq_ignore_ascii_case("india") {
+            pin_code = validate_pin_code(&pin_code)?;
+            state = canonical_state(&state)
+                .ok_or_else(|| {
+                    StoreError::validation(format!("\"{state}\" is not an Indian state"))
+                })?
+                .to_string();
+        }
+
+        Ok(Address {
+            address_id: self.address_id,
+            description: optional(&self.description),
+            house_num: optional(&self.house_num),
+            street_name: optional(&self.street_name),
+            locality_name: optional(&self.locality_name),
+            city_name: required("City", &self.city_name)?.to_string(),
+            district_name: required("District", &self.district_name)?.to_string(),
+            pin_code,
+            state,
+            country: country.to_string(),
+        })
+    }
+}
+
+/// Indian PIN codes are six digits and never start with 0. Spaces, as in
+/// "110 001", are accepted and removed.
+pub fn validate_pin_code(pin_code: &str) -> Result<String> {
+    let digits: String = pin_code.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let valid =
+        digits.len() == 6 && digits.chars().all(|c| c.is_ascii_digit()) && !digits.starts_with('0');
+
+    if !valid {
+        return Err(StoreError::validation(format!(
+            "\"{pin_code}\" is not a valid PIN code"
+        )));
+    }
+    Ok(digits)
+}
+
+/// Matches `state` against `INDIAN_STATES` ignoring case and extra spaces.
+pub fn canonical_state(state: &str) -> Option<&'static str> {
+    let state = state.split_whitespace().collect::<Vec<_>>().join(" ");
+    INDIAN_STATES
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(&state))
+        .copied()
+}
+
+/// The cities each dealer has an address in, keyed by dealer.
+pub(crate) fn load_dealer_cities(connection: &Connection) -> Result<HashMap<i64, Vec<String>>> {
+    let mut cities: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut statement = connection.prepare(
+        "
+            SELECT dl.dealer_id, a.city_name
+            FROM dealer_location dl
+            JOIN address a ON a.address_id = dl.address_id
+            ",
+    )?;
+    let mut rows = statement.query(())?;
+    while let Some(row) = rows.next()? {
+        cities.entry(row.get(0)?).or_default().push(row.get(1)?);
+    }
+    Ok(cities)
+}
+
+impl Store {
+    pub fn get_dealer_addresses(&self, dealer_id: i64) -> Result<Vec<Address>> {
+        self.ensure_exists("dealer", dealer_id)?;
+
+        let addresses = self
+            .connection
+            .prepare(
+                "
+                    SELECT a.address_id, dl.description, a.house_num, a.street_name, a.locality_name,
+                        a.city_name, a.district_name, a.pin_code, a.state, a.country
+                    FROM dealer_location dl
+                    JOIN address a ON a.address_id = dl.address_id
+                    WHERE dl.dealer_id = ?1
+                    ORDER BY a.address_id
+                    ",
+            )?
+            .query_map(params![dealer_id], |row| {
+                Ok(Address {
+                    address_id: row.get(0)?,
+                    description: row.get(1)?,
+                    house_num: row.get(2)?,
+                    street_name: row.get(3)?,
+                    locality_name: row.get(4)?,
+                    city_name: row.get(5)?,
+                    district_name: row.get(6)?,
+                    pin_code: row.get(7)?,
+                    state: row.get(8)?,
+                    country: row.get(9)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(addresses)
+    }
+
+    /// Adds a new address for the dealer. `address.address_id` is ignored.
+    pub fn add_dealer_address(&mut self, dealer_id: i64, address: &Address) -> Result<i64> {
+        let transaction = self.connection.transaction()?;
+        let address_id = insert_address(&transaction, dealer_id, address)?;
+        transaction.commit()?;
+        Ok(address_id)
+    }
+
+    /// Overwrites the dealer's address identified by `address.address_id`.
+    pub fn update_dealer_address(&mut self, dealer_id: i64, address: &Address) -> Result<()> {
+        let address = address.normalized()?;
+        self.ensure_dealer_location(dealer_id, address.address_id)?;
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "
+            UPDATE address
+            SET house_num = ?2, street_name = ?3, locality_name = ?4, city_name = ?5,
+                district_name = ?6, pin_code = ?7, state = ?8, country = ?9
+            WHERE address_id = ?1",
+            params![
+                address.address_id,
+                address.house_num,
+                address.street_name,
+                address.locality_name,
+                address.city_name,
+                address.district_name,
+                address.pin_code,
+                address.state,
+                address.country
+            ],
+        )?;
+
+        transaction.execute(
+            "UPDATE dealer_location SET description = ?3 WHERE dealer_id = ?1 AND address_id = ?2",
+            params![dealer_id, address.address_id, address.description],
+        )?;
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Detaches the address from the dealer and deletes it once no dealer uses it.
+    pub fn remove_dealer_address(&mut self, dealer_id: i64, address_id: i64) -> Result<()> {
+        self.ensure_dealer_location(dealer_id, address_id)?;
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "DELETE FROM dealer_location WHERE dealer_id = ?1 AND address_id = ?2",
+            params![dealer_id, address_id],
+        )?;
+        transaction.execute(
+            "
+            DELETE FROM address
+            WHERE address_id = ?1
+            AND NOT EXISTS (SELECT 1 FROM dealer_location WHERE address_id = ?1)",
+            params![address_id],
+        )?;
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn ensure_dealer_location(&self, dealer_id: i64, address_id: i64) -> Result<()> {
+        self.connection
+            .query_row(
+                "SELECT 1 FROM dealer_location WHERE dealer_id = ?1 AND address_id = ?2",
+                params![dealer_id, address_id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .ok_or(StoreError::NotFound("address"))
+    }
+}
+
+/// What `add_dealer_address` does, inside the caller's transaction.
+pub(crate) fn insert_address(
+    connection: &Connection,
+    dealer_id: i64,
+    address: &Address,
+) -> Result<i64> {
+    ensure_exists(connection, "dealer", dealer_id)?;
+    let address = address.normalized()?;
+
+    connection.execute(
+        "
+        INSERT INTO address (house_num, street_name, locality_name, city_name, district_name, pin_code, state, country)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            address.house_num,
+            address.street_name,
+            address.locality_name,
+            address.city_name,
+            address.district_name,
+            address.pin_code,
+            address.state,
+            address.country
+        ],
+    )?;
+    let address_id = connection.last_insert_rowid();
+
+    connection.execute(
+        "INSERT INTO dealer_location (dealer_id, address_id, description) VALUES (?1, ?2, ?3)",
+        params![dealer_id, address_id, address.description],
+    )?;
+
+    Ok(address_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn godown() -> Address {
+        Address {
+            description: Some("godown".to_string()),
+            street_name: Some("  ".to_string()),
+            city_name: "Indore".to_string(),
+            district_name: "Indore".to_string(),
+            pin_code: "452 001".to_string(),
+            state: "madhya  pradesh".to_string(),
+            country: "India".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pin_codes_are_validated() {
+        assert_eq!(validate_pin_code("110001").unwrap(), "110001");
+        assert_eq!(validate_pin_code(" 110 001 ").unwrap(), "110001");
+        assert!(validate_pin_code("011001").is_err());
+        assert!(validate_pin_code("11001").is_err());
+        assert!(validate_pin_code("11000A").is_err());
+    }
+
+    #[test]
+    fn states_are_canonicalised() {
+        assert_eq!(canonical_state("tamil nadu"), Some("Tamil Nadu"));
+        assert_eq!(canonical_state(" DELHI "), Some("Delhi"));
+        assert_eq!(canonical_state("Bombay"), None);
+    }
+
+    #[test]
+    fn foreign_addresses_skip_indian_checks() {
+        let address = Address {
+            city_name: "Kathmandu".to_string(),
+            district_name: "Kathmandu".to_string(),
+            pin_code: "44600".to_string(),
+            state: "Bagmati".to_string(),
+            country: "Nepal".to_string(),
+            ..Default::default()
+        };
+        assert!(address.normalized().is_ok());
+    }
+
+    #[test]
+    fn dealer_addresses_round_trip() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let dealer_id = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+
+        let address_id = store.add_dealer_address(dealer_id, &godown()).unwrap();
+        let shop = Address {
+            description: Some("shop".to_string()),
+            ..godown()
+        };
+        store.add_dealer_address(dealer_id, &shop).unwrap();
+
+        let addresses = store.get_dealer_addresses(dealer_id).unwrap();
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(addresses[0].address_id, address_id);
+        assert_eq!(addresses[0].pin_code, "452001");
+        assert_eq!(addresses[0].state, "Madhya Pradesh");
+        assert_eq!(addresses[0].street_name, None);
+
+        let mut edited = addresses[0].clone();
+        edited.house_num = Some("12".to_string());
+        edited.description = Some("old godown".to_string());
+        store.update_dealer_address(dealer_id, &edited).unwrap();
+        assert_eq!(store.get_dealer_addresses(dealer_id).unwrap()[0], edited);
+
+        store.remove_dealer_address(dealer_id, address_id).unwrap();
+        assert_eq!(store.get_dealer_addresses(dealer_id).unwrap().len(), 1);
+        let orphans: i64 = store
+            .connection
+            .query_row(
+                "SELECT COUNT(*) FROM address WHERE address_id = ?1",
+                [address_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(orphans, 0);
+    }
+
+    #[test]
+    fn invalid_addresses_are_rejected() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let dealer_id = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+
+        let bad_pin = Address {
+            pin_code: "12".to_string(),
+            ..godown()
+        };
+        assert!(matches!(
+            store.add_dealer_address(dealer_id, &bad_pin),
+            Err(StoreError::Validation(_))
+        ));
+        assert!(matches!(
+            store.add_dealer_address(dealer_id + 1, &godown()),
+            Err(StoreError::NotFound("dealer"))
+        ));
+        assert!(matches!(
+            store.remove_dealer_address(dealer_id, 99),
+            Err(StoreError::NotFound("address"))
+        ));
+    }
+}
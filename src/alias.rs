@@ -0,0 +1,258 @@
+use crate::{required, Result, Store, StoreError};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+
+/// Common Hindi grocery words, in Latin and Devanagari spelling, for the item
+/// names they usually stand for. `add_starter_aliases` attaches them to the
+/// items of those names.
+pub const STARTER_ALIASES: &[(&str, &[&str])] = &[
+    ("Wheat Flour", &["atta", "आटा"]),
+    ("Refined Flour", &["maida", "मैदा"]),
+    ("Gram Flour", &["besan", "बेसन"]),
+    ("Semolina", &["suji", "sooji", "rava", "सूजी"]),
+    ("Rice", &["chawal", "चावल"]),
+    ("Flattened Rice", &["poha", "पोहा"]),
+    ("Sago", &["sabudana", "साबूदाना"]),
+    ("Vermicelli", &["seviyan", "सेवइयां"]),
+    ("Lentils", &["dal", "daal", "दाल"]),
+    ("Chickpeas", &["chana", "चना"]),
+    ("Kidney Beans", &["rajma", "राजमा"]),
+    ("Sugar", &["chini", "cheeni", "shakkar", "चीनी", "शक्कर"]),
+    ("Jaggery", &["gud", "गुड़"]),
+    ("Salt", &["namak", "नमक"]),
+    ("Oil", &["tel", "तेल"]),
+    ("Mustard Oil", &["sarson tel", "सरसों तेल"]),
+    ("Milk", &["doodh", "दूध"]),
+    ("Curd", &["dahi", "दही"]),
+    ("Butter", &["makhan", "मक्खन"]),
+    ("Cottage Cheese", &["paneer", "पनीर"]),
+    ("Tea", &["chai", "चाय"]),
+    ("Turmeric", &["haldi", "हल्दी"]),
+    ("Chilli Powder", &["lal mirch", "लाल मिर्च"]),
+    ("Black Pepper", &["kali mirch", "काली मिर्च"]),
+    ("Coriander", &["dhaniya", "धनिया"]),
+    ("Cumin", &["jeera", "जीरा"]),
+    ("Mustard Seeds", &["rai", "sarson", "राई", "सरसों"]),
+    ("Fenugreek", &["methi", "मेथी"]),
+    ("Carom Seeds", &["ajwain", "अजवाइन"]),
+    ("Fennel Seeds", &["saunf", "सौंफ"]),
+    ("Cardamom", &["elaichi", "इलायची"]),
+    ("Cloves", &["laung", "लौंग"]),
+    ("Asafoetida", &["hing", "हींग"]),
+    ("Soap", &["sabun", "साबुन"]),
+    ("Matches", &["machis", "माचिस"]),
+];
+
+/// What an alias is another name for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasTarget {
+    Product(i64),
+    Brand(i64),
+    Item(i64),
+}
+
+impl AliasTarget {
+    /// Reads the target back from the `product_id`, `brand_id` and `item_id`
+    /// columns of an alias row, of which exactly one is set.
+    fn from_columns(
+        product_id: Option<i64>,
+        brand_id: Option<i64>,
+        item_id: Option<i64>,
+    ) -> Option<Self> {
+        match (product_id, brand_id, item_id) {
+            (Some(id), _, _) => Some(AliasTarget::Product(id)),
+            (_, Some(id), _) => Some(AliasTarget::Brand(id)),
+            (_, _, Some(id)) => Some(AliasTarget::Item(id)),
+            _ => None,
+        }
+    }
+
+    fn columns(self) -> (Option<i64>, Option<i64>, Option<i64>) {
+        match self {
+            AliasTarget::Product(id) => (Some(id), None, None),
+            AliasTarget::Brand(id) => (None, Some(id), None),
+            AliasTarget::Item(id) => (None, None, Some(id)),
+        }
+    }
+}
+
+/// Another name a product, brand or item is searched by, e.g. "chini" for
+/// sugar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    pub alias_id: i64,
+    pub name: String,
+    pub target: AliasTarget,
+}
+
+/// Every alias, lowercase and grouped by what it names, for ranking.
+#[derive(Debug, Default)]
+pub(crate) struct AliasNames {
+    pub products: HashMap<i64, Vec<String>>,
+    pub brands: HashMap<i64, Vec<String>>,
+    pub items: HashMap<i64, Vec<String>>,
+}
+
+impl Store {
+    pub fn add_alias(&mut self, name: &str, target: AliasTarget) -> Result<i64> {
+        let name = required("Alias", name)?;
+        match target {
+            AliasTarget::Product(id) => self.ensure_exists("product", id)?,
+            AliasTarget::Brand(id) => self.ensure_exists("brand", id)?,
+            AliasTarget::Item(id) => self.ensure_exists("item", id)?,
+        }
+
+        let (product_id, brand_id, item_id) = target.columns();
+        self.connection
+            .execute(
+                "INSERT INTO alias (name, product_id, brand_id, item_id) VALUES (?1, ?2, ?3, ?4)",
+                params![name, product_id, brand_id, item_id],
+            )
+            .map_err(StoreError::duplicate("alias"))?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    pub fn delete_alias(&mut self, alias_id: i64) -> Result<()> {
+        self.ensure_exists("alias", alias_id)?;
+        self.connection
+            .execute("DELETE FROM alias WHERE alias_id = ?1", params![alias_id])?;
+        Ok(())
+    }
+
+    /// The aliases a product is searched by: its own, then its brand's, then
+    /// its item's, each alphabetically.
+    pub fn get_product_aliases(&self, product_id: i64) -> Result<Vec<Alias>> {
+        self.ensure_exists("product", product_id)?;
+        let aliases = self
+            .connection
+            .prepare(
+                "
+                SELECT alias.alias_id, alias.name, alias.product_id, alias.brand_id, alias.item_id
+                FROM alias, product
+                WHERE product.product_id = ?1
+                AND (alias.product_id = product.product_id
+                     OR alias.brand_id = product.brand_id
+                     OR alias.item_id = product.item_id)
+                ORDER BY alias.product_id IS NULL, alias.brand_id IS NULL,
+                    alias.name COLLATE NOCASE
+                ",
+            )?
+            .query_map(params![product_id], |row| {
+                let target =
+                    AliasTarget::from_columns(row.get(2)?, row.get(3)?, row.get(4)?).ok_or(
+                        rusqlite::Error::InvalidColumnType(4, "item_id".into(), Type::Null),
+                    )?;
+                Ok(Alias {
+                    alias_id: row.get(0)?,
+                    name: row.get(1)?,
+                    target,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(aliases)
+    }
+
+    /// Gives every item named in `STARTER_ALIASES` the Hindi names listed
+    /// for it, skipping the ones it already has. Returns how many were added.
+    pub fn add_starter_aliases(&mut self) -> Result<usize> {
+        let transaction = self.connection.transaction()?;
+        let mut added = 0;
+        for (item_name, aliases) in STARTER_ALIASES {
+            for alias in *aliases {
+                added += transaction.execute(
+                    "
+                    INSERT OR IGNORE INTO alias (name, item_id)
+                    SELECT ?2, item_id FROM item
+                    WHERE name = ?1 COLLATE NOCASE AND name <> ?2 COLLATE NOCASE
+                    ",
+                    params![item_name, alias],
+                )?;
+            }
+        }
+        transaction.commit()?;
+        Ok(added)
+    }
+}
+
+pub(crate) fn load_alias_names(connection: &Connection) -> Result<AliasNames> {
+    let mut names = AliasNames::default();
+    let mut statement =
+        connection.prepare("SELECT name, product_id, brand_id, item_id FROM alias")?;
+    let mut rows = statement.query(())?;
+    while let Some(row) = rows.next()? {
+        let name = row.get::<_, String>(0)?.to_lowercase();
+        let (map, id) = match AliasTarget::from_columns(row.get(1)?, row.get(2)?, row.get(3)?) {
+            Some(AliasTarget::Product(id)) => (&mut names.products, id),
+            Some(AliasTarget::Brand(id)) => (&mut names.brands, id),
+            Some(AliasTarget::Item(id)) => (&mut names.items, id),
+            None => continue,
+        };
+        map.entry(id).or_default().push(name);
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn aliases_find_products_by_other_names() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let sugar = store
+            .add_product("Refined Sugar", "Madhur", "Sugar", "1kg", None)
+            .unwrap();
+        let oil = store
+            .add_product("Kachi Pressed", "Fortune", "Mustard Oil", "1L", None)
+            .unwrap();
+        let flour = store
+            .add_product("Whole Wheat", "Aashirvaad", "Wheat Flour", "5kg", None)
+            .unwrap();
+
+        let best = |store: &Store, text: &str| -> Vec<i64> {
+            store
+                .get_best_product_results_for(text)
+                .unwrap()
+                .iter()
+                .map(|product| product.product_id)
+                .collect()
+        };
+        assert!(best(&store, "chini").is_empty());
+
+        assert_eq!(store.add_starter_aliases().unwrap(), 9);
+        assert_eq!(store.add_starter_aliases().unwrap(), 0);
+        assert_eq!(best(&store, "chini"), vec![sugar]);
+        assert_eq!(best(&store, "चीनी"), vec![sugar]);
+        assert_eq!(best(&store, "atta 5kg"), vec![flour]);
+        assert_eq!(best(&store, "item:tel"), vec![oil]);
+
+        let fortune = store.get_product(oil).unwrap().brand_id;
+        store
+            .add_alias("Dhara", AliasTarget::Brand(fortune))
+            .unwrap();
+        let kachi = store.add_alias("KGMO", AliasTarget::Product(oil)).unwrap();
+        assert!(matches!(
+            store.add_alias("kgmo", AliasTarget::Product(oil)),
+            Err(StoreError::Duplicate("alias"))
+        ));
+        assert_eq!(best(&store, "dhara"), vec![oil]);
+        assert_eq!(best(&store, "kgmo"), vec![oil]);
+
+        let names: Vec<_> = store
+            .get_product_aliases(oil)
+            .unwrap()
+            .into_iter()
+            .map(|alias| alias.name)
+            .collect();
+        assert_eq!(names, vec!["KGMO", "Dhara", "sarson tel", "सरसों तेल"]);
+
+        store.delete_alias(kachi).unwrap();
+        assert!(best(&store, "kgmo").is_empty());
+        assert!(matches!(
+            store.delete_alias(kachi),
+            Err(StoreError::NotFound("alias"))
+        ));
+    }
+}
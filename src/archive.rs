@@ -0,0 +1,299 @@
+use crate::{
+    contact, dealer_from_row, product_from_row, Dealer, Product, Result, Store, DEALER_QUERY,
+    PRODUCT_QUERY,
+};
+use rusqlite::{params, Connection};
+
+/// What a hard delete takes with it. Deleting a brand or an item cascades to
+/// every product under it, and deleting a product or dealer cascades to its
+/// prices and dealer links.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeletionImpact {
+    pub products: usize,
+    pub prices: usize,
+    pub links: usize,
+}
+
+impl Store {
+    /// Hides the product from searches, lists and the data feed. Its prices
+    /// and links are kept and `restore_product` brings it back.
+    pub fn archive_product(&mut self, product_id: i64) -> Result<()> {
+        self.set_archived("product", product_id, true)
+    }
+
+    pub fn restore_product(&mut self, product_id: i64) -> Result<()> {
+        self.set_archived("product", product_id, false)
+    }
+
+    /// Hides the dealer from the dealer list, the comparison table and the
+    /// data feed. Its prices and links are kept and `restore_dealer` brings
+    /// it back.
+    pub fn archive_dealer(&mut self, dealer_id: i64) -> Result<()> {
+        self.set_archived("dealer", dealer_id, true)
+    }
+
+    pub fn restore_dealer(&mut self, dealer_id: i64) -> Result<()> {
+        self.set_archived("dealer", dealer_id, false)
+    }
+
+    /// Archived products, most recently archived first.
+    pub fn get_archived_products(&self) -> Result<Vec<Product>> {
+        let products = self
+            .connection
+            .prepare(&format!(
+                "{PRODUCT_QUERY}
+                WHERE product.archived_at IS NOT NULL
+                ORDER BY product.archived_at DESC, product.product_id"
+            ))?
+            .query_map((), product_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(products)
+    }
+
+    /// Archived dealers, most recently archived first.
+    pub fn get_archived_dealers(&self) -> Result<Vec<Dealer>> {
+        let mut dealers = self
+            .connection
+            .prepare(&format!(
+                "{DEALER_QUERY}
+                WHERE archived_at IS NOT NULL
+                ORDER BY archived_at DESC, dealer_id"
+            ))?
+            .query_map((), dealer_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for dealer in &mut dealers {
+            dealer.contacts = contact::load_contacts(&self.connection, dealer.dealer_id)?;
+        }
+        Ok(dealers)
+    }
+
+    pub fn preview_product_deletion(&self, product_id: i64) -> Result<DeletionImpact> {
+        self.ensure_exists("product", product_id)?;
+        self.deletion_impact("product_id = ?1", "product_id = ?1", product_id)
+    }
+
+    pub fn preview_dealer_deletion(&self, dealer_id: i64) -> Result<DeletionImpact> {
+        self.ensure_exists("dealer", dealer_id)?;
+        self.deletion_impact("0", "dealer_id = ?1", dealer_id)
+    }
+
+    pub fn preview_brand_deletion(&self, brand_id: i64) -> Result<DeletionImpact> {
+        self.ensure_exists("brand", brand_id)?;
+        self.deletion_impact(
+            "brand_id = ?1",
+            "product_id IN (SELECT product_id FROM product WHERE brand_id = ?1)",
+            brand_id,
+        )
+    }
+
+    pub fn preview_item_deletion(&self, item_id: i64) -> Result<DeletionImpact> {
+        self.ensure_exists("item", item_id)?;
+        self.deletion_impact(
+            "item_id = ?1",
+            "product_id IN (SELECT product_id FROM product WHERE item_id = ?1)",
+            item_id,
+        )
+    }
+
+    /// Deletes the product with its prices and links for good, returning
+    /// what went with it.
+    pub fn delete_product(&mut self, product_id: i64) -> Result<DeletionImpact> {
+        let impact = self.preview_product_deletion(product_id)?;
+        self.connection.execute(
+            "DELETE FROM product WHERE product_id = ?1",
+            params![product_id],
+        )?;
+        Ok(impact)
+    }
+
+    /// Deletes the dealer with its prices and links for good, along with any
+    /// phone numbers and addresses no other dealer uses.
+    pub fn delete_dealer(&mut self, dealer_id: i64) -> Result<DeletionImpact> {
+        let impact = self.preview_dealer_deletion(dealer_id)?;
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "DELETE FROM dealer WHERE dealer_id = ?1",
+            params![dealer_id],
+        )?;
+        transaction.execute(
+            "DELETE FROM phone WHERE phone_id NOT IN (SELECT phone_id FROM dealer_contact)",
+            (),
+        )?;
+        transaction.execute(
+            "DELETE FROM address WHERE address_id NOT IN (SELECT address_id FROM dealer_location)",
+            (),
+        )?;
+
+        transaction.commit()?;
+        Ok(impact)
+    }
+
+    /// Deletes the brand and, through the schema's cascade, every product of
+    /// that brand with their prices and links.
+    pub fn delete_brand(&mut self, brand_id: i64) -> Result<DeletionImpact> {
+        let impact = self.preview_brand_deletion(brand_id)?;
+        self.connection
+            .execute("DELETE FROM brand WHERE brand_id = ?1", params![brand_id])?;
+        Ok(impact)
+    }
+
+    /// Deletes the item and, through the schema's cascade, every product of
+    /// that item with their prices and links.
+    pub fn delete_item(&mut self, item_id: i64) -> Result<DeletionImpact> {
+        let impact = self.preview_item_deletion(item_id)?;
+        self.connection
+            .execute("DELETE FROM item WHERE item_id = ?1", params![item_id])?;
+        Ok(impact)
+    }
+
+    fn set_archived(&mut self, table: &'static str, id: i64, archived: bool) -> Result<()> {
+        self.ensure_exists(table, id)?;
+        set_archived(&self.connection, table, id, archived)
+    }
+
+    /// Counts the products matching `products` and the prices and links
+    /// matching `rows`. Both filters take the id as `?1`.
+    fn deletion_impact(&self, products: &str, rows: &str, id: i64) -> Result<DeletionImpact> {
+        let impact = self.connection.query_row(
+            &format!(
+                "
+                SELECT
+                    (SELECT COUNT(*) FROM product WHERE {products}),
+                    (SELECT COUNT(*) FROM dealer_price WHERE {rows}),
+                    (SELECT COUNT(*) FROM dealer_product WHERE {rows})
+                "
+            ),
+            params![id],
+            |row| {
+                Ok(DeletionImpact {
+                    products: row.get(0)?,
+                    prices: row.get(1)?,
+                    links: row.get(2)?,
+                })
+            },
+        )?;
+        Ok(impact)
+    }
+}
+
+/// Archives or restores the `table` ("product" or "dealer") row `id`. An
+/// archived row keeps the time it was first archived.
+pub(crate) fn set_archived(
+    connection: &Connection,
+    table: &str,
+    id: i64,
+    archived: bool,
+) -> Result<()> {
+    let archived_at = if archived {
+        "COALESCE(archived_at, datetime('now'))"
+    } else {
+        "NULL"
+    };
+    connection.execute(
+        &format!("UPDATE {table} SET archived_at = {archived_at} WHERE {table}_id = ?1"),
+        params![id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, SearchContext, StoreError};
+    use std::path::Path;
+
+    #[test]
+    fn archived_rows_leave_searches_but_keep_history() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let ramesh = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+        store
+            .update_price(butter, ramesh, Money::from_rupees(250))
+            .unwrap();
+        store
+            .record_product_selection(SearchContext::DataFeed, butter)
+            .unwrap();
+
+        store.archive_product(butter).unwrap();
+        assert!(store.get_products().unwrap().is_empty());
+        assert!(store
+            .get_best_product_results_for("butter")
+            .unwrap()
+            .is_empty());
+        assert!(store
+            .get_recent_product_results(SearchContext::DataFeed)
+            .unwrap()
+            .is_empty());
+        assert!(store.get_product(butter).unwrap().archived);
+        assert_eq!(store.get_archived_products().unwrap().len(), 1);
+        assert_eq!(store.get_price_history(butter, ramesh).unwrap().len(), 1);
+
+        store.restore_product(butter).unwrap();
+        assert_eq!(store.get_products().unwrap().len(), 1);
+
+        store.archive_dealer(ramesh).unwrap();
+        assert!(store.get_dealers().unwrap().is_empty());
+        assert!(store.get_dealer_price_pairs_for(butter).unwrap().is_empty());
+        assert_eq!(store.get_archived_dealers().unwrap()[0].dealer_id, ramesh);
+        store.restore_dealer(ramesh).unwrap();
+        assert_eq!(store.get_dealer_price_pairs_for(butter).unwrap().len(), 1);
+
+        assert!(matches!(
+            store.archive_product(42),
+            Err(StoreError::NotFound("product"))
+        ));
+    }
+
+    #[test]
+    fn deleting_a_brand_previews_and_takes_its_products() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let cheese = store
+            .add_product("Cheese", "Amul", "Cheese", "200g", None)
+            .unwrap();
+        let oil = store
+            .add_product("Sunflower Oil", "Fortune", "Oil", "1L", None)
+            .unwrap();
+        let ramesh = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+        for (product_id, rupees) in [(butter, 250), (cheese, 120), (oil, 180)] {
+            store
+                .update_price(product_id, ramesh, Money::from_rupees(rupees))
+                .unwrap();
+        }
+
+        let amul = store.get_product(butter).unwrap().brand_id;
+        let expected = DeletionImpact {
+            products: 2,
+            prices: 2,
+            links: 2,
+        };
+        assert_eq!(store.preview_brand_deletion(amul).unwrap(), expected);
+        assert_eq!(store.delete_brand(amul).unwrap(), expected);
+
+        let left: Vec<_> = store
+            .get_products()
+            .unwrap()
+            .iter()
+            .map(|product| product.product_id)
+            .collect();
+        assert_eq!(left, vec![oil]);
+        assert_eq!(store.get_dealer_products(ramesh).unwrap().len(), 1);
+
+        assert_eq!(
+            store.delete_dealer(ramesh).unwrap(),
+            DeletionImpact {
+                products: 0,
+                prices: 1,
+                links: 1,
+            }
+        );
+        assert!(store.get_product_price_history(oil).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{Result, Store, StoreError};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+
+const BOOST_POINTS_SETTING: &str = "selection_boost_points";
+const HALF_LIFE_SETTING: &str = "selection_half_life_days";
+pub const DEFAULT_BOOST_POINTS: u32 = 300;
+pub const DEFAULT_HALF_LIFE_DAYS: u32 = 30;
+
+/// How much picking a product from the search dropdowns lifts it in later
+/// searches.
+///
+/// Every pick adds one to the product's weight, and the weight halves every
+/// `half_life_days`, so frequent and recent picks count the most. A weight
+/// of `w` lifts the product by `max_points * w / (w + 1)`: half of
+/// `max_points` for one fresh pick, approaching all of it for many. The
+/// default is enough to reorder similar matches but not to put a near miss
+/// ahead of an exact one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectionWeighting {
+    /// In the points search scores are counted in. 0 turns the boost off.
+    pub max_points: u32,
+    pub half_life_days: u32,
+}
+
+impl Default for SelectionWeighting {
+    fn default() -> Self {
+        SelectionWeighting {
+            max_points: DEFAULT_BOOST_POINTS,
+            half_life_days: DEFAULT_HALF_LIFE_DAYS,
+        }
+    }
+}
+
+impl Store {
+    pub fn selection_weighting(&self) -> Result<SelectionWeighting> {
+        let number = |name, default| -> Result<u32> {
+            Ok(self
+                .setting(name)?
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default))
+        };
+        Ok(SelectionWeighting {
+            max_points: number(BOOST_POINTS_SETTING, DEFAULT_BOOST_POINTS)?,
+            half_life_days: number(HALF_LIFE_SETTING, DEFAULT_HALF_LIFE_DAYS)?,
+        })
+    }
+
+    pub fn set_selection_weighting(&mut self, weighting: SelectionWeighting) -> Result<()> {
+        if weighting.half_life_days == 0 {
+            return Err(StoreError::validation(
+                "Picks must count for at least a day",
+            ));
+        }
+
+        self.set_setting(BOOST_POINTS_SETTING, &weighting.max_points.to_string())?;
+        self.set_setting(HALF_LIFE_SETTING, &weighting.half_life_days.to_string())?;
+        Ok(())
+    }
+
+    /// Forgets every pick, so that searches rank on the match alone again.
+    pub fn reset_selection_boosts(&mut self) -> Result<()> {
+        self.connection
+            .execute("DELETE FROM product_selection", ())?;
+        Ok(())
+    }
+
+    /// How many points each picked product is lifted by right now.
+    pub(crate) fn selection_boosts(&self) -> Result<HashMap<i64, usize>> {
+        let weighting = self.selection_weighting()?;
+        if weighting.max_points == 0 {
+            return Ok(HashMap::new());
+        }
+
+        let boosts = self
+            .connection
+            .prepare(
+                "SELECT product_id, weight, julianday('now') - julianday(selected_at)
+                 FROM product_selection",
+            )?
+            .query_map((), |row| {
+                let weight = decay(row.get(1)?, row.get(2)?, weighting.half_life_days);
+                let boost = weighting.max_points as f64 * weight / (weight + 1.0);
+                Ok((row.get(0)?, boost.round() as usize))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(boosts)
+    }
+}
+
+/// Adds a pick of the product at `selected_at`, which SQLite's `datetime()`
+/// must understand, to its weight. The weight is kept as of the latest pick,
+/// so a pick older than that counts for what it has decayed to by then.
+pub(crate) fn count_selection(
+    connection: &Connection,
+    product_id: i64,
+    selected_at: &str,
+    half_life_days: u32,
+) -> Result<()> {
+    let previous: Option<(f64, f64)> = connection
+        .query_row(
+            "SELECT weight, julianday(?2) - julianday(selected_at)
+             FROM product_selection WHERE product_id = ?1",
+            params![product_id, selected_at],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let weight = match previous {
+        None => 1.0,
+        Some((weight, age)) if age >= 0.0 => decay(weight, age, half_life_days) + 1.0,
+        Some((weight, age)) => weight + decay(1.0, -age, half_life_days),
+    };
+
+    connection.execute(
+        "INSERT INTO product_selection (product_id, weight, selected_at) VALUES (?1, ?2, datetime(?3))
+         ON CONFLICT (product_id) DO UPDATE
+         SET weight = excluded.weight, selected_at = MAX(selected_at, excluded.selected_at)",
+        params![product_id, weight, selected_at],
+    )?;
+    Ok(())
+}
+
+/// What `weight` is worth `age_days` later.
+fn decay(weight: f64, age_days: f64, half_life_days: u32) -> f64 {
+    weight * 0.5f64.powf(age_days.max(0.0) / half_life_days as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchContext;
+    use std::path::Path;
+
+    #[test]
+    fn picked_products_float_up_and_fade() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let small = store
+            .add_product("Butter", "Amul", "Butter", "100g", None)
+            .unwrap();
+        let large = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+
+        let first = |store: &Store, text: &str| -> i64 {
+            store.get_best_product_results_for(text).unwrap()[0].product_id
+        };
+
+        store
+            .record_product_selection(SearchContext::DataFeed, large)
+            .unwrap();
+        assert_eq!(first(&store, "butter"), large);
+        // A pick does not outweigh a better match.
+        assert_eq!(first(&store, "butter 100g"), small);
+
+        // Picks from long ago have faded away.
+        for _ in 0..3 {
+            store
+                .record_product_selection_at(
+                    SearchContext::Comparison,
+                    small,
+                    "2020-01-01 10:00:00",
+                )
+                .unwrap();
+        }
+        assert_eq!(first(&store, "butter"), large);
+
+        // More picks beat fewer.
+        for _ in 0..3 {
+            store
+                .record_product_selection(SearchContext::Comparison, small)
+                .unwrap();
+        }
+        assert_eq!(first(&store, "butter"), small);
+
+        store
+            .set_selection_weighting(SelectionWeighting {
+                max_points: 0,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(store.selection_boosts().unwrap().is_empty());
+        assert!(matches!(
+            store.set_selection_weighting(SelectionWeighting {
+                half_life_days: 0,
+                ..Default::default()
+            }),
+            Err(StoreError::Validation(_))
+        ));
+
+        store
+            .set_selection_weighting(SelectionWeighting::default())
+            .unwrap();
+        store.reset_selection_boosts().unwrap();
+        assert!(store.selection_boosts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn late_recorded_picks_count_as_of_the_latest() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let record = |store: &mut Store, selected_at: &str| {
+            store.record_product_selection_at(SearchContext::Comparison, butter, selected_at)
+        };
+        let stored = |store: &Store| -> (f64, String) {
+            store
+                .connection
+                .query_row(
+                    "SELECT weight, selected_at FROM product_selection WHERE product_id = ?1",
+                    params![butter],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .unwrap()
+        };
+
+        record(&mut store, "2024-03-01 00:00:00").unwrap();
+        // `DEFAULT_HALF_LIFE_DAYS` before the pick already counted.
+        record(&mut store, "2024-01-31 00:00:00").unwrap();
+        let (weight, selected_at) = stored(&store);
+        assert!((weight - 1.5).abs() < 1e-9, "{weight}");
+        assert_eq!(selected_at, "2024-03-01 00:00:00");
+
+        assert!(matches!(
+            record(&mut store, "last tuesday"),
+            Err(StoreError::Validation(_))
+        ));
+        assert_eq!(stored(&store).1, "2024-03-01 00:00:00");
+    }
+}
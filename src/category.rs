@@ -0,0 +1,345 @@
+use crate::{ensure_exists, required, Product, Result, Store, StoreError};
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// A node of the category tree, e.g. "Dairy" under "Grocery". `depth` is the
+/// distance from the root of the listing the category came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Category {
+    pub category_id: i64,
+    pub name: String,
+    pub parent_id: Option<i64>,
+    pub depth: u32,
+}
+
+/// Walks the tree down from the categories picked by `{roots}`, visiting
+/// siblings in name order. `path` sorts every node right after its parent.
+const TREE_QUERY: &str = "
+    WITH RECURSIVE tree (category_id, name, parent_id, depth, path) AS (
+        SELECT category_id, name, parent_id, 0, name || char(31)
+        FROM category
+        WHERE {roots}
+        UNION ALL
+        SELECT c.category_id, c.name, c.parent_id, tree.depth + 1, tree.path || c.name || char(31)
+        FROM category c
+        JOIN tree ON c.parent_id = tree.category_id
+    )
+    SELECT category_id, name, parent_id, depth FROM tree ORDER BY path COLLATE NOCASE
+    ";
+
+impl Store {
+    /// Every category, depth first with siblings in name order.
+    pub fn get_categories(&self) -> Result<Vec<Category>> {
+        self.query_tree("parent_id IS NULL", params![])
+    }
+
+    /// The category followed by everything below it.
+    pub fn get_category_subtree(&self, category_id: i64) -> Result<Vec<Category>> {
+        self.ensure_exists("category", category_id)?;
+        self.query_tree("category_id = ?1", params![category_id])
+    }
+
+    pub fn add_category(&mut self, name: &str, parent_id: Option<i64>) -> Result<i64> {
+        insert_category(&self.connection, name, parent_id)
+    }
+
+    pub fn rename_category(&mut self, category_id: i64, name: &str) -> Result<()> {
+        let name = category_name(name)?;
+        let parent_id = self.category_parent(category_id)?;
+        ensure_unique_sibling(&self.connection, name, parent_id, Some(category_id))?;
+
+        self.connection.execute(
+            "UPDATE category SET name = ?2 WHERE category_id = ?1",
+            params![category_id, name],
+        )?;
+        Ok(())
+    }
+
+    /// Moves the category, with everything below it, under `parent_id`, or to
+    /// the top level for `None`.
+    pub fn move_category(&mut self, category_id: i64, parent_id: Option<i64>) -> Result<()> {
+        self.category_parent(category_id)?;
+
+        if let Some(parent_id) = parent_id {
+            let is_own_descendant = self
+                .get_category_subtree(category_id)?
+                .iter()
+                .any(|category| category.category_id == parent_id);
+            if is_own_descendant {
+                return Err(StoreError::validation(
+                    "A category cannot be moved under itself or one of its subcategories",
+                ));
+            }
+            self.ensure_exists("category", parent_id)?;
+        }
+
+        let name: String = self.connection.query_row(
+            "SELECT name FROM category WHERE category_id = ?1",
+            params![category_id],
+            |row| row.get(0),
+        )?;
+        ensure_unique_sibling(&self.connection, &name, parent_id, Some(category_id))?;
+
+        self.connection.execute(
+            "UPDATE category SET parent_id = ?2 WHERE category_id = ?1",
+            params![category_id, parent_id],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes the category. Its subcategories move up to its parent, or to
+    /// the top level, and its items become uncategorized, as the schema's
+    /// `ON DELETE SET NULL` says. A subcategory named like one already there
+    /// is a `Duplicate` and nothing is deleted.
+    pub fn remove_category(&mut self, category_id: i64) -> Result<()> {
+        let parent_id = self.category_parent(category_id)?;
+
+        let collides: bool = self.connection.query_row(
+            "
+            SELECT EXISTS (
+                SELECT 1 FROM category child
+                JOIN category sibling ON sibling.name = child.name COLLATE NOCASE
+                WHERE child.parent_id = ?1 AND sibling.parent_id IS ?2 AND sibling.category_id <> ?1
+            )",
+            params![category_id, parent_id],
+            |row| row.get(0),
+        )?;
+        if collides {
+            return Err(StoreError::Duplicate("category"));
+        }
+
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "UPDATE category SET parent_id = ?2 WHERE parent_id = ?1",
+            params![category_id, parent_id],
+        )?;
+        transaction.execute(
+            "DELETE FROM category WHERE category_id = ?1",
+            params![category_id],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Products whose item is filed under the category or anything below it.
+    pub fn get_products_in_category(&self, category_id: i64) -> Result<Vec<Product>> {
+        let category_ids: Vec<i64> = self
+            .get_category_subtree(category_id)?
+            .iter()
+            .map(|category| category.category_id)
+            .collect();
+
+        Ok(self
+            .get_products()?
+            .into_iter()
+            .filter(|product| {
+                product
+                    .category_id
+                    .is_some_and(|id| category_ids.contains(&id))
+            })
+            .collect())
+    }
+
+    fn query_tree(&self, roots: &str, params: &[&dyn rusqlite::ToSql]) -> Result<Vec<Category>> {
+        let categories = self
+            .connection
+            .prepare(&TREE_QUERY.replace("{roots}", roots))?
+            .query_map(params, |row| {
+                Ok(Category {
+                    category_id: row.get(0)?,
+                    name: row.get(1)?,
+                    parent_id: row.get(2)?,
+                    depth: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(categories)
+    }
+
+    fn category_parent(&self, category_id: i64) -> Result<Option<i64>> {
+        self.connection
+            .query_row(
+                "SELECT parent_id FROM category WHERE category_id = ?1",
+                params![category_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(StoreError::NotFound("category"))
+    }
+}
+
+/// What `add_category` does, inside the caller's transaction.
+pub(crate) fn insert_category(
+    connection: &Connection,
+    name: &str,
+    parent_id: Option<i64>,
+) -> Result<i64> {
+    let name = category_name(name)?;
+    if let Some(parent_id) = parent_id {
+        ensure_exists(connection, "category", parent_id)?;
+    }
+    ensure_unique_sibling(connection, name, parent_id, None)?;
+
+    connection.execute(
+        "INSERT INTO category (name, parent_id) VALUES (?1, ?2)",
+        params![name, parent_id],
+    )?;
+    Ok(connection.last_insert_rowid())
+}
+
+/// Trims `name`, which may not contain the ">" that separates the names in
+/// a category path like "Grocery > Oil".
+fn category_name(name: &str) -> Result<&str> {
+    let name = required("Category name", name)?;
+    if name.contains('>') {
+        return Err(StoreError::validation(
+            "Category names cannot contain \">\"",
+        ));
+    }
+    Ok(name)
+}
+
+/// Sibling categories must have different names, ignoring case.
+fn ensure_unique_sibling(
+    connection: &Connection,
+    name: &str,
+    parent_id: Option<i64>,
+    category_id: Option<i64>,
+) -> Result<()> {
+    let taken: bool = connection.query_row(
+        "
+        SELECT EXISTS (
+            SELECT 1 FROM category
+            WHERE parent_id IS ?1 AND name = ?2 COLLATE NOCASE AND category_id IS NOT ?3
+        )",
+        params![parent_id, name, category_id],
+        |row| row.get(0),
+    )?;
+
+    if taken {
+        return Err(StoreError::Duplicate("category"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn names(categories: &[Category]) -> Vec<(&str, u32)> {
+        categories
+            .iter()
+            .map(|category| (category.name.as_str(), category.depth))
+            .collect()
+    }
+
+    #[test]
+    fn categories_form_a_tree() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let grocery = store.add_category("Grocery", None).unwrap();
+        let dairy = store.add_category("Dairy", Some(grocery)).unwrap();
+        store.add_category("Butter", Some(dairy)).unwrap();
+        let oil = store.add_category("Oil", Some(grocery)).unwrap();
+        store.add_category("Cleaning", None).unwrap();
+
+        assert_eq!(
+            names(&store.get_categories().unwrap()),
+            vec![
+                ("Cleaning", 0),
+                ("Grocery", 0),
+                ("Dairy", 1),
+                ("Butter", 2),
+                ("Oil", 1)
+            ]
+        );
+        assert_eq!(
+            names(&store.get_category_subtree(dairy).unwrap()),
+            vec![("Dairy", 0), ("Butter", 1)]
+        );
+
+        assert!(matches!(
+            store.add_category("dairy", Some(grocery)),
+            Err(StoreError::Duplicate("category"))
+        ));
+        store.rename_category(oil, "Edible Oil").unwrap();
+        store.move_category(dairy, None).unwrap();
+        assert_eq!(
+            names(&store.get_categories().unwrap()),
+            vec![
+                ("Cleaning", 0),
+                ("Dairy", 0),
+                ("Butter", 1),
+                ("Grocery", 0),
+                ("Edible Oil", 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn categories_cannot_be_moved_into_themselves() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let grocery = store.add_category("Grocery", None).unwrap();
+        let dairy = store.add_category("Dairy", Some(grocery)).unwrap();
+
+        for parent in [grocery, dairy] {
+            assert!(matches!(
+                store.move_category(grocery, Some(parent)),
+                Err(StoreError::Validation(_))
+            ));
+        }
+        assert!(matches!(
+            store.move_category(dairy, Some(99)),
+            Err(StoreError::NotFound("category"))
+        ));
+    }
+
+    #[test]
+    fn removing_a_category_keeps_its_children_and_items() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let grocery = store.add_category("Grocery", None).unwrap();
+        let dairy = store.add_category("Dairy", Some(grocery)).unwrap();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", Some(dairy))
+            .unwrap();
+        store
+            .add_product("Sunflower Oil", "Fortune", "Oil", "1L", None)
+            .unwrap();
+
+        let in_grocery = store.get_products_in_category(grocery).unwrap();
+        assert_eq!(in_grocery.len(), 1);
+        assert_eq!(in_grocery[0].product_id, butter);
+
+        store.remove_category(grocery).unwrap();
+        assert_eq!(names(&store.get_categories().unwrap()), vec![("Dairy", 0)]);
+        assert_eq!(store.get_products_in_category(dairy).unwrap().len(), 1);
+
+        store.remove_category(dairy).unwrap();
+        assert_eq!(store.get_products().unwrap()[0].category_id, None);
+    }
+
+    #[test]
+    fn removing_a_category_keeps_sibling_names_unique() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let grocery = store.add_category("Grocery", None).unwrap();
+        let dairy = store.add_category("Dairy", Some(grocery)).unwrap();
+        let cheese = store.add_category("Cheese", Some(dairy)).unwrap();
+        store.add_category("dairy", None).unwrap();
+
+        assert!(matches!(
+            store.remove_category(grocery),
+            Err(StoreError::Duplicate("category"))
+        ));
+        assert_eq!(store.get_categories().unwrap().len(), 4);
+
+        // Subcategories move up one level, not to the top.
+        store.remove_category(dairy).unwrap();
+        assert_eq!(
+            names(&store.get_categories().unwrap()),
+            vec![("dairy", 0), ("Grocery", 0), ("Cheese", 1)]
+        );
+        assert_eq!(
+            store.get_category_subtree(grocery).unwrap()[1].category_id,
+            cheese
+        );
+    }
+}
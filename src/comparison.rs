@@ -0,0 +1,132 @@
+use crate::{Dealer, Measure, Money, Product, ProductFilter, Result, Store, UnitPrice};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// A dealer's latest price for one product of an item.
+#[derive(Debug)]
+pub struct ItemOffer {
+    pub product: Product,
+    pub dealer: Dealer,
+    pub price: Money,
+    /// `None` when the product's pack size could not be read.
+    pub unit_price: Option<UnitPrice>,
+}
+
+impl Store {
+    /// The latest price of every active dealer for every active product of
+    /// the item, cheapest per kilogram, litre or piece first, so that brands
+    /// and pack sizes can be weighed against each other.
+    ///
+    /// Unit prices are only comparable within a measure, so the offers are
+    /// grouped by it, the measure most of them share first. Offers whose
+    /// pack size is unknown come last, cheapest first.
+    pub fn get_item_comparison(&self, item_id: i64) -> Result<Vec<ItemOffer>> {
+        self.ensure_exists("item", item_id)?;
+        let products = self.search_products(&ProductFilter {
+            item_id: Some(item_id),
+            ..Default::default()
+        })?;
+
+        let mut offers = Vec::new();
+        for product in products {
+            for (dealer, price) in self.get_latest_dealer_price_pairs_for(product.product_id)? {
+                offers.push(ItemOffer {
+                    unit_price: product.pack.map(|pack| pack.unit_price(price)),
+                    product: product.clone(),
+                    dealer,
+                    price,
+                });
+            }
+        }
+
+        let mut measures: HashMap<Measure, usize> = HashMap::new();
+        for unit_price in offers.iter().filter_map(|offer| offer.unit_price) {
+            *measures.entry(unit_price.measure).or_default() += 1;
+        }
+        offers.sort_by_key(|offer| {
+            let group = offer.unit_price.map(|unit_price| {
+                (
+                    Reverse(measures[&unit_price.measure]),
+                    unit_price.measure as u8,
+                )
+            });
+            (
+                group.is_none(),
+                group,
+                offer.unit_price.map(|unit_price| unit_price.price),
+                offer.price,
+            )
+        });
+        Ok(offers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn cheapest_per_unit_comes_first() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let amul = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let mother_dairy = store
+            .add_product("Butter", "Mother Dairy", "Butter", "1kg", None)
+            .unwrap();
+        let chiplets = store
+            .add_product("Butter Chiplets", "Amul", "Butter", "100 pcs", None)
+            .unwrap();
+        let tub = store
+            .add_product("Butter Tub", "Britannia", "Butter", "Tub", None)
+            .unwrap();
+        let ramesh = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+        let suresh = store.add_dealer("Suresh", None, "Jain", &[]).unwrap();
+
+        store
+            .update_price(amul, ramesh, Money::from_rupees(130))
+            .unwrap();
+        store
+            .update_price(amul, suresh, Money::from_rupees(125))
+            .unwrap();
+        store
+            .update_price(mother_dairy, ramesh, Money::from_rupees(240))
+            .unwrap();
+        store
+            .update_price(chiplets, suresh, Money::from_rupees(300))
+            .unwrap();
+        store
+            .update_price(tub, ramesh, Money::from_rupees(90))
+            .unwrap();
+
+        let item_id = store.get_product(amul).unwrap().item_id;
+        let offers: Vec<_> = store
+            .get_item_comparison(item_id)
+            .unwrap()
+            .iter()
+            .map(|offer| {
+                (
+                    offer.product.product_id,
+                    offer.dealer.dealer_id,
+                    offer.unit_price.map(|unit_price| unit_price.to_string()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            offers,
+            vec![
+                (mother_dairy, ramesh, Some("₹240.00/kg".to_string())),
+                (amul, suresh, Some("₹250.00/kg".to_string())),
+                (amul, ramesh, Some("₹260.00/kg".to_string())),
+                (chiplets, suresh, Some("₹3.00/pc".to_string())),
+                (tub, ramesh, None),
+            ]
+        );
+
+        assert!(matches!(
+            store.get_item_comparison(999),
+            Err(crate::StoreError::NotFound("item"))
+        ));
+    }
+}
@@ -0,0 +1,475 @@
+use crate::{required, Result, Store, StoreError};
+use phonenumber::metadata::DATABASE;
+use phonenumber::{country, Mode, PhoneNumber};
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Numbers typed without a country code are taken to be Indian.
+pub const DEFAULT_COUNTRY_CODE: &str = "+91";
+
+/// A phone number a dealer can be reached on. `label` tells the numbers
+/// apart, e.g. "office", "salesman Ramesh" or "WhatsApp".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Contact {
+    pub phone_id: i64,
+    pub country_code: String,
+    pub phone_number: String,
+    pub label: Option<String>,
+    pub is_primary: bool,
+}
+
+impl Contact {
+    /// The number in E.164 form, e.g. "+919876543210".
+    pub fn e164(&self) -> String {
+        format!("{}{}", self.country_code, self.phone_number)
+    }
+
+    /// The number as it should be shown: national format for Indian numbers,
+    /// international format for the rest. Numbers that do not parse are shown
+    /// as stored.
+    pub fn formatted(&self) -> String {
+        match parse_phone(&self.country_code, &self.phone_number) {
+            Some(number) if number.code().value() == 91 => {
+                number.format().mode(Mode::National).to_string()
+            }
+            Some(number) => number.format().mode(Mode::International).to_string(),
+            None => format!("{} {}", self.country_code, self.phone_number),
+        }
+    }
+}
+
+/// Parses a number as typed into the country code and number boxes. A number
+/// written with its own "+" prefix ignores `country_code`, and a blank
+/// `country_code` means `DEFAULT_COUNTRY_CODE`.
+fn parse_phone(country_code: &str, phone_number: &str) -> Option<PhoneNumber> {
+    let phone_number = phone_number.trim();
+    if phone_number.is_empty() {
+        return None;
+    }
+
+    if phone_number.starts_with('+') {
+        return phonenumber::parse(None, phone_number)
+            .ok()
+            .filter(PhoneNumber::is_valid);
+    }
+
+    // Parsing against the country's region, rather than gluing the code on
+    // with a "+", lets the national trunk prefix ("0" in India) be dropped.
+    let country_code = match country_code.trim() {
+        "" => DEFAULT_COUNTRY_CODE,
+        country_code => country_code,
+    };
+    let code: u16 = country_code.trim_start_matches('+').parse().ok()?;
+    let region = DATABASE
+        .region(&code)?
+        .first()?
+        .parse::<country::Id>()
+        .ok()?;
+
+    phonenumber::parse(Some(region), phone_number)
+        .ok()
+        .filter(|number| number.is_valid() && number.code().value() == code)
+}
+
+/// Validates the number and splits its E.164 form into "+<country code>" and
+/// the national number, which is how the `phone` table stores it.
+pub fn normalize_phone(country_code: &str, phone_number: &str) -> Result<(String, String)> {
+    let number = parse_phone(country_code, phone_number).ok_or_else(|| {
+        StoreError::validation(format!(
+            "\"{}\" is not a valid phone number",
+            phone_number.trim()
+        ))
+    })?;
+
+    let country_code = format!("+{}", number.code().value());
+    let e164 = number.format().mode(Mode::E164).to_string();
+    let national_number = e164[country_code.len()..].to_string();
+    Ok((country_code, national_number))
+}
+
+fn optional_label(label: Option<&str>) -> Option<&str> {
+    label.map(str::trim).filter(|label| !label.is_empty())
+}
+
+/// Inserts the phone and links it to the dealer. The caller decides which
+/// contact is primary and must keep it to one per dealer.
+pub(crate) fn insert_contact(
+    connection: &Connection,
+    dealer_id: i64,
+    contact: &Contact,
+    is_primary: bool,
+) -> Result<i64> {
+    let phone_number = required("Phone number", &contact.phone_number)?;
+    let (country_code, phone_number) = normalize_phone(&contact.country_code, phone_number)?;
+
+    connection.execute(
+        "INSERT INTO phone (country_code, phone_number) VALUES (?1, ?2)",
+        params![country_code, phone_number],
+    )?;
+    let phone_id = connection.last_insert_rowid();
+
+    connection.execute(
+        "INSERT INTO dealer_contact (dealer_id, phone_id, description, is_primary) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            dealer_id,
+            phone_id,
+            optional_label(contact.label.as_deref()),
+            is_primary
+        ],
+    )?;
+    Ok(phone_id)
+}
+
+/// The dealer's numbers, primary first.
+pub(crate) fn load_contacts(connection: &Connection, dealer_id: i64) -> Result<Vec<Contact>> {
+    let contacts = connection
+        .prepare_cached(
+            "
+                SELECT p.phone_id, p.country_code, p.phone_number, dc.description, dc.is_primary
+                FROM dealer_contact dc
+                JOIN phone p ON p.phone_id = dc.phone_id
+                WHERE dc.dealer_id = ?1
+                ORDER BY dc.is_primary DESC, p.phone_id
+                ",
+        )?
+        .query_map(params![dealer_id], |row| {
+            Ok(Contact {
+                phone_id: row.get(0)?,
+                country_code: row.get(1)?,
+                phone_number: row.get(2)?,
+                label: row.get(3)?,
+                is_primary: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(contacts)
+}
+
+impl Store {
+    pub fn get_dealer_contacts(&self, dealer_id: i64) -> Result<Vec<Contact>> {
+        self.ensure_exists("dealer", dealer_id)?;
+        load_contacts(&self.connection, dealer_id)
+    }
+
+    /// Adds a number to the dealer. It becomes the primary contact if the
+    /// dealer had none or `contact.is_primary` is set.
+    pub fn add_dealer_contact(&mut self, dealer_id: i64, contact: &Contact) -> Result<i64> {
+        self.ensure_exists("dealer", dealer_id)?;
+
+        let transaction = self.connection.transaction()?;
+
+        let has_primary: bool = transaction.query_row(
+            "SELECT EXISTS (SELECT 1 FROM dealer_contact WHERE dealer_id = ?1 AND is_primary)",
+            params![dealer_id],
+            |row| row.get(0),
+        )?;
+        let is_primary = contact.is_primary || !has_primary;
+        if is_primary {
+            transaction.execute(
+                "UPDATE dealer_contact SET is_primary = 0 WHERE dealer_id = ?1",
+                params![dealer_id],
+            )?;
+        }
+        let phone_id = insert_contact(&transaction, dealer_id, contact, is_primary)?;
+
+        transaction.commit()?;
+        Ok(phone_id)
+    }
+
+    /// Removes the number. If it was the primary one, the oldest remaining
+    /// number takes its place.
+    pub fn remove_dealer_contact(&mut self, dealer_id: i64, phone_id: i64) -> Result<()> {
+        self.ensure_dealer_contact(dealer_id, phone_id)?;
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "DELETE FROM dealer_contact WHERE dealer_id = ?1 AND phone_id = ?2",
+            params![dealer_id, phone_id],
+        )?;
+        transaction.execute(
+            "
+            DELETE FROM phone
+            WHERE phone_id = ?1
+            AND NOT EXISTS (SELECT 1 FROM dealer_contact WHERE phone_id = ?1)",
+            params![phone_id],
+        )?;
+        transaction.execute(
+            "
+            UPDATE dealer_contact SET is_primary = 1
+            WHERE dealer_id = ?1
+            AND NOT EXISTS (SELECT 1 FROM dealer_contact WHERE dealer_id = ?1 AND is_primary)
+            AND phone_id = (SELECT MIN(phone_id) FROM dealer_contact WHERE dealer_id = ?1)",
+            params![dealer_id],
+        )?;
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Overwrites the number and label of the dealer's contact identified by
+    /// `contact.phone_id`. Whether it is the primary contact does not change.
+    pub fn update_dealer_contact(&mut self, dealer_id: i64, contact: &Contact) -> Result<()> {
+        let phone_number = required("Phone number", &contact.phone_number)?;
+        let (country_code, phone_number) = normalize_phone(&contact.country_code, phone_number)?;
+        self.ensure_dealer_contact(dealer_id, contact.phone_id)?;
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "UPDATE phone SET country_code = ?2, phone_number = ?3 WHERE phone_id = ?1",
+            params![contact.phone_id, country_code, phone_number],
+        )?;
+        transaction.execute(
+            "UPDATE dealer_contact SET description = ?3 WHERE dealer_id = ?1 AND phone_id = ?2",
+            params![
+                dealer_id,
+                contact.phone_id,
+                optional_label(contact.label.as_deref())
+            ],
+        )?;
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    pub fn set_contact_label(
+        &mut self,
+        dealer_id: i64,
+        phone_id: i64,
+        label: Option<&str>,
+    ) -> Result<()> {
+        self.ensure_dealer_contact(dealer_id, phone_id)?;
+
+        self.connection.execute(
+            "UPDATE dealer_contact SET description = ?3 WHERE dealer_id = ?1 AND phone_id = ?2",
+            params![dealer_id, phone_id, optional_label(label)],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_primary_contact(&mut self, dealer_id: i64, phone_id: i64) -> Result<()> {
+        self.ensure_dealer_contact(dealer_id, phone_id)?;
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "UPDATE dealer_contact SET is_primary = 0 WHERE dealer_id = ?1",
+            params![dealer_id],
+        )?;
+        transaction.execute(
+            "UPDATE dealer_contact SET is_primary = 1 WHERE dealer_id = ?1 AND phone_id = ?2",
+            params![dealer_id, phone_id],
+        )?;
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Numbers that are not stored in normalized form, with the dealer each
+    /// belongs to. Only rows the normalization pass could not parse end up
+    /// here, and they stay as typed until someone corrects them.
+    pub fn get_unparsed_contacts(&self) -> Result<Vec<(i64, Contact)>> {
+        let contacts = self
+            .connection
+            .prepare(
+                "
+                    SELECT dc.dealer_id, p.phone_id, p.country_code, p.phone_number, dc.description, dc.is_primary
+                    FROM dealer_contact dc
+                    JOIN phone p ON p.phone_id = dc.phone_id
+                    ORDER BY dc.dealer_id, p.phone_id
+                    ",
+            )?
+            .query_map((), |row| {
+                let contact = Contact {
+                    phone_id: row.get(1)?,
+                    country_code: row.get(2)?,
+                    phone_number: row.get(3)?,
+                    label: row.get(4)?,
+                    is_primary: row.get(5)?,
+                };
+                Ok((row.get(0)?, contact))
+            })?
+            .collect::<rusqlite::Result<Vec<(i64, Contact)>>>()?;
+
+        Ok(contacts
+            .into_iter()
+            .filter(|(_, contact)| {
+                normalize_phone(&contact.country_code, &contact.phone_number).ok()
+                    != Some((contact.country_code.clone(), contact.phone_number.clone()))
+            })
+            .collect())
+    }
+
+    fn ensure_dealer_contact(&self, dealer_id: i64, phone_id: i64) -> Result<()> {
+        self.connection
+            .query_row(
+                "SELECT 1 FROM dealer_contact WHERE dealer_id = ?1 AND phone_id = ?2",
+                params![dealer_id, phone_id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .ok_or(StoreError::NotFound("contact"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn contact(phone_number: &str, label: Option<&str>) -> Contact {
+        Contact {
+            country_code: "+91".to_string(),
+            phone_number: phone_number.to_string(),
+            label: label.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dealers_own_all_their_numbers() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let dealer_id = store
+            .add_dealer(
+                "Ramesh",
+                None,
+                "Gupta",
+                &[
+                    contact("9876543210", Some("office")),
+                    contact("9876543211", Some("WhatsApp")),
+                ],
+            )
+            .unwrap();
+
+        let dealers = store.get_dealers().unwrap();
+        assert_eq!(dealers.len(), 1);
+        let contacts = &dealers[0].contacts;
+        assert_eq!(contacts.len(), 2);
+        assert!(contacts[0].is_primary);
+        assert_eq!(contacts[0].label.as_deref(), Some("office"));
+        assert!(!contacts[1].is_primary);
+
+        let whatsapp = contacts[1].phone_id;
+        store.set_primary_contact(dealer_id, whatsapp).unwrap();
+        store
+            .set_contact_label(dealer_id, whatsapp, Some("WhatsApp, evenings"))
+            .unwrap();
+        let primary = store.get_dealer_contacts(dealer_id).unwrap().remove(0);
+        assert_eq!(primary.phone_id, whatsapp);
+        assert_eq!(primary.label.as_deref(), Some("WhatsApp, evenings"));
+    }
+
+    #[test]
+    fn removing_the_primary_promotes_another() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let dealer_id = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+
+        let first = store
+            .add_dealer_contact(dealer_id, &contact("9876543210", None))
+            .unwrap();
+        let second = store
+            .add_dealer_contact(dealer_id, &contact("9876543211", Some("salesman Suresh")))
+            .unwrap();
+        assert!(store.get_dealer_contacts(dealer_id).unwrap()[0].is_primary);
+
+        store.remove_dealer_contact(dealer_id, first).unwrap();
+
+        let contacts = store.get_dealer_contacts(dealer_id).unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].phone_id, second);
+        assert!(contacts[0].is_primary);
+        assert!(matches!(
+            store.remove_dealer_contact(dealer_id, first),
+            Err(StoreError::NotFound("contact"))
+        ));
+    }
+
+    #[test]
+    fn changed_numbers_are_updated_in_place() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let dealer_id = store
+            .add_dealer("Ramesh", None, "Gupta", &[contact("9876543210", None)])
+            .unwrap();
+        let mut changed = store.get_dealer_contacts(dealer_id).unwrap().remove(0);
+
+        changed.phone_number = "098765 43219".to_string();
+        changed.label = Some("new shop".to_string());
+        store.update_dealer_contact(dealer_id, &changed).unwrap();
+
+        let contacts = store.get_dealer_contacts(dealer_id).unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].phone_id, changed.phone_id);
+        assert_eq!(contacts[0].phone_number, "9876543219");
+        assert_eq!(contacts[0].label.as_deref(), Some("new shop"));
+        assert!(contacts[0].is_primary);
+
+        changed.phone_number = "12345".to_string();
+        assert!(matches!(
+            store.update_dealer_contact(dealer_id, &changed),
+            Err(StoreError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn numbers_are_normalized_and_formatted() {
+        assert_eq!(
+            normalize_phone("+91", "098765-43210").unwrap(),
+            ("+91".to_string(), "9876543210".to_string())
+        );
+        assert_eq!(
+            normalize_phone("", "98765 43210").unwrap(),
+            ("+91".to_string(), "9876543210".to_string())
+        );
+        assert_eq!(
+            normalize_phone("+91", "+44 20 7946 0958").unwrap(),
+            ("+44".to_string(), "2079460958".to_string())
+        );
+        assert!(normalize_phone("+91", "12345").is_err());
+
+        let indian = contact("9876543210", None);
+        assert_eq!(indian.e164(), "+919876543210");
+        assert_eq!(indian.formatted(), "098765 43210");
+        let british = Contact {
+            country_code: "+44".to_string(),
+            phone_number: "2079460958".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(british.formatted(), "+44 20 7946 0958");
+    }
+
+    #[test]
+    fn invalid_numbers_are_rejected_and_reported() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let dealer_id = store
+            .add_dealer("Ramesh", None, "Gupta", &[contact("98765 43210", None)])
+            .unwrap();
+        assert_eq!(
+            store.get_dealer_contacts(dealer_id).unwrap()[0].phone_number,
+            "9876543210"
+        );
+        assert!(matches!(
+            store.add_dealer_contact(dealer_id, &contact("555", None)),
+            Err(StoreError::Validation(_))
+        ));
+        assert!(store.get_unparsed_contacts().unwrap().is_empty());
+
+        store
+            .connection
+            .execute(
+                "INSERT INTO phone (phone_id, country_code, phone_number) VALUES (99, '+91', 'ask at shop')",
+                (),
+            )
+            .unwrap();
+        store
+            .connection
+            .execute(
+                "INSERT INTO dealer_contact (dealer_id, phone_id) VALUES (?1, 99)",
+                [dealer_id],
+            )
+            .unwrap();
+        let unparsed = store.get_unparsed_contacts().unwrap();
+        assert_eq!(unparsed.len(), 1);
+        assert_eq!(unparsed[0].0, dealer_id);
+        assert_eq!(unparsed[0].1.phone_id, 99);
+    }
+}
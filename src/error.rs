@@ -0,0 +1,166 @@
+use rusqlite::ErrorCode;
+use std::fmt;
+use std::io;
+
+pub type Result<T, E = StoreError> = std::result::Result<T, E>;
+
+/// Everything that can go wrong inside the store layer. The `&'static str`
+/// carried by `NotFound` and `Duplicate` names the kind of record involved,
+/// e.g. "dealer" or "product".
+#[derive(Debug)]
+pub enum StoreError {
+    NotFound(&'static str),
+    Duplicate(&'static str),
+    Validation(String),
+    /// Search text that does not follow the query syntax. The message says
+    /// what to fix, for showing next to the search box.
+    InvalidQuery(String),
+    ConstraintViolation(String),
+    Io(io::Error),
+    SchemaTooNew {
+        found: u32,
+        supported: u32,
+    },
+    MigrationFailed {
+        version: u32,
+        description: &'static str,
+        source: rusqlite::Error,
+    },
+    Database(rusqlite::Error),
+}
+
+impl StoreError {
+    pub(crate) fn validation(message: impl Into<String>) -> Self {
+        StoreError::Validation(message.into())
+    }
+
+    /// For `map_err` on a statement whose only UNIQUE or PRIMARY KEY
+    /// failure means `what` already exists. Other errors convert as usual.
+    pub(crate) fn duplicate(what: &'static str) -> impl FnOnce(rusqlite::Error) -> Self {
+        move |e| {
+            if is_unique_violation(&e) {
+                StoreError::Duplicate(what)
+            } else {
+                e.into()
+            }
+        }
+    }
+}
+
+fn is_unique_violation(e: &rusqlite::Error) -> bool {
+    matches!(
+        e,
+        rusqlite::Error::SqliteFailure(failure, _)
+            if failure.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE
+                || failure.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY
+    )
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::NotFound(what) => write!(f, "No such {what} exists"),
+            StoreError::Duplicate(what) => write!(f, "This {what} already exists"),
+            StoreError::Validation(message) | StoreError::InvalidQuery(message) => {
+                write!(f, "{message}")
+            }
+            StoreError::ConstraintViolation(message) => {
+                write!(f, "The change was rejected by the database: {message}")
+            }
+            StoreError::Io(e) => write!(f, "Unable to access the store file: {e}"),
+            StoreError::SchemaTooNew { found, supported } => write!(
+                f,
+                "The store was written by a newer version of this program (schema {found}, \
+                 this version supports up to {supported})"
+            ),
+            StoreError::MigrationFailed {
+                version,
+                description,
+                source,
+            } => write!(
+                f,
+                "Upgrading the store to version {version} ({description}) failed: {source}"
+            ),
+            StoreError::Database(e) => write!(f, "Database error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StoreError::Io(e) => Some(e),
+            StoreError::MigrationFailed { source, .. } => Some(source),
+            StoreError::Database(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Which record a missing row or a UNIQUE failure is about is only known
+/// where the statement runs, so those stay `Database` errors here. Call
+/// sites map them with `.optional()` and `StoreError::duplicate`.
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::SqliteFailure(failure, message)
+                if failure.code == ErrorCode::ConstraintViolation && !is_unique_violation(&e) =>
+            {
+                StoreError::ConstraintViolation(message.unwrap_or_else(|| failure.to_string()))
+            }
+            e => StoreError::Database(e),
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    #[test]
+    fn sqlite_errors_are_classified() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE t (name TEXT UNIQUE NOT NULL CHECK (LENGTH(name) > 0));
+                 INSERT INTO t VALUES ('amul');",
+            )
+            .unwrap();
+
+        let duplicate = || {
+            connection
+                .execute("INSERT INTO t VALUES ('amul')", ())
+                .unwrap_err()
+        };
+        assert!(matches!(
+            StoreError::from(duplicate()),
+            StoreError::Database(_)
+        ));
+        assert!(matches!(
+            StoreError::duplicate("brand")(duplicate()),
+            StoreError::Duplicate("brand")
+        ));
+
+        let check = connection
+            .execute("INSERT INTO t VALUES ('')", ())
+            .unwrap_err();
+        assert!(matches!(
+            StoreError::duplicate("brand")(check),
+            StoreError::ConstraintViolation(_)
+        ));
+
+        let missing = connection
+            .query_row("SELECT name FROM t WHERE name = 'x'", (), |row| {
+                row.get::<_, String>(0)
+            })
+            .unwrap_err();
+        assert!(matches!(StoreError::from(missing), StoreError::Database(_)));
+    }
+}
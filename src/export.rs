@@ -0,0 +1,717 @@
+use crate::{
+    contact, dealer_from_row, product_from_row, Address, Category, Contact, Dealer, Money, Product,
+    Result, Store, DEALER_QUERY, PRODUCT_QUERY,
+};
+use csv::Writer;
+use rusqlite::params;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// One row of `dealer_price`.
+struct PriceRecord {
+    product_id: i64,
+    dealer_id: i64,
+    price: Money,
+    recorded_at: String,
+}
+
+/// Everything in the store, archived records included, read once so that
+/// the files written from it agree with each other.
+struct Snapshot {
+    /// Parents before their children.
+    categories: Vec<Category>,
+    products: Vec<Product>,
+    dealers: Vec<(Dealer, Vec<Address>)>,
+    /// Oldest first.
+    prices: Vec<PriceRecord>,
+}
+
+impl Store {
+    /// The category tree as the `Categories` importer reads it, each parent
+    /// by its path.
+    pub fn export_categories_csv(&self) -> Result<String> {
+        self.snapshot()?.categories_csv()
+    }
+
+    /// Every product, archived ones included, as the `Products` importer
+    /// reads them.
+    pub fn export_products_csv(&self) -> Result<String> {
+        self.snapshot()?.products_csv()
+    }
+
+    /// Every dealer with their phone numbers, archived ones included, as the
+    /// `Dealers` importer reads them.
+    pub fn export_dealers_csv(&self) -> Result<String> {
+        self.snapshot()?.dealers_csv()
+    }
+
+    /// Every dealer address, as the `Addresses` importer reads them.
+    pub fn export_addresses_csv(&self) -> Result<String> {
+        self.snapshot()?.addresses_csv()
+    }
+
+    /// The whole `dealer_price` history, oldest first, as the `Prices`
+    /// importer reads it.
+    pub fn export_prices_csv(&self) -> Result<String> {
+        self.snapshot()?.prices_csv()
+    }
+
+    /// Categories, products, dealers with their contacts and addresses, and
+    /// the whole price history as one JSON document. Records refer to each
+    /// other by their ids in this store; `import_json` matches those to the
+    /// records they become in another.
+    pub fn export_json(&self) -> Result<String> {
+        let json = self.snapshot()?.json();
+        Ok(serde_json::to_string_pretty(&json).map_err(io::Error::from)?)
+    }
+
+    /// Writes categories.csv, products.csv, dealers.csv, addresses.csv,
+    /// prices.csv and store.json into `directory`, creating it if needed and
+    /// replacing files from an earlier export. The CSV files import into
+    /// another store in that order, or store.json on its own.
+    pub fn export_to(&self, directory: &Path) -> Result<()> {
+        let snapshot = self.snapshot()?;
+        fs::create_dir_all(directory)?;
+
+        for (name, contents) in [
+            ("categories.csv", snapshot.categories_csv()?),
+            ("products.csv", snapshot.products_csv()?),
+            ("dealers.csv", snapshot.dealers_csv()?),
+            ("addresses.csv", snapshot.addresses_csv()?),
+            ("prices.csv", snapshot.prices_csv()?),
+        ] {
+            fs::write(directory.join(name), contents)?;
+        }
+        let json = serde_json::to_string_pretty(&snapshot.json()).map_err(io::Error::from)?;
+        fs::write(directory.join("store.json"), json)?;
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Result<Snapshot> {
+        let products = self
+            .connection
+            .prepare(&format!("{PRODUCT_QUERY} ORDER BY product.product_id"))?
+            .query_map((), product_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut dealers = Vec::new();
+        let all_dealers = self
+            .connection
+            .prepare(&format!("{DEALER_QUERY} ORDER BY dealer_id"))?
+            .query_map((), dealer_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for mut dealer in all_dealers {
+            dealer.contacts = contact::load_contacts(&self.connection, dealer.dealer_id)?;
+            let addresses = self.get_dealer_addresses(dealer.dealer_id)?;
+            dealers.push((dealer, addresses));
+        }
+
+        let prices = self
+            .connection
+            .prepare(
+                "
+                SELECT product_id, dealer_id, price_paise, time_stamp FROM dealer_price
+                ORDER BY time_stamp, product_id, dealer_id
+                ",
+            )?
+            .query_map(params![], |row| {
+                Ok(PriceRecord {
+                    product_id: row.get(0)?,
+                    dealer_id: row.get(1)?,
+                    price: row.get(2)?,
+                    recorded_at: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(Snapshot {
+            categories: self.get_categories()?,
+            products,
+            dealers,
+            prices,
+        })
+    }
+}
+
+impl Snapshot {
+    /// Each category's path, like "Grocery > Dairy", which unlike its name
+    /// only it has.
+    fn category_paths(&self) -> HashMap<i64, String> {
+        let mut paths = HashMap::new();
+        for category in &self.categories {
+            let path = match category.parent_id {
+                Some(parent_id) => format!("{} > {}", paths[&parent_id], category.name),
+                None => category.name.clone(),
+            };
+            paths.insert(category.category_id, path);
+        }
+        paths
+    }
+
+    fn categories_csv(&self) -> Result<String> {
+        let paths = self.category_paths();
+        write_csv(
+            &["name", "parent"],
+            self.categories.iter().map(|category| {
+                vec![
+                    category.name.clone(),
+                    optional(
+                        category
+                            .parent_id
+                            .map(|parent_id| paths[&parent_id].as_str()),
+                    ),
+                ]
+            }),
+        )
+    }
+
+    fn products_csv(&self) -> Result<String> {
+        let paths = self.category_paths();
+        write_csv(
+            &["product", "brand", "item", "pack", "category", "archived"],
+            self.products.iter().map(|product| {
+                vec![
+                    product.product_name.clone(),
+                    product.brand_name.clone(),
+                    product.item_name.clone(),
+                    product.pack_name.clone(),
+                    optional(
+                        product
+                            .category_id
+                            .map(|category_id| paths[&category_id].as_str()),
+                    ),
+                    yes_or_blank(product.archived),
+                ]
+            }),
+        )
+    }
+
+    /// Numbers the dealers who share a first and last name with another,
+    /// ignoring case, from 1 in the order they were added. The importers
+    /// tell namesakes with no phone number apart by these.
+    fn namesakes(&self) -> HashMap<i64, usize> {
+        let mut named: HashMap<(String, String), Vec<i64>> = HashMap::new();
+        for (dealer, _) in &self.dealers {
+            named
+                .entry((
+                    dealer.first_name.to_ascii_lowercase(),
+                    dealer.last_name.to_ascii_lowercase(),
+                ))
+                .or_default()
+                .push(dealer.dealer_id);
+        }
+        named
+            .into_values()
+            .filter(|dealer_ids| dealer_ids.len() > 1)
+            .flat_map(|dealer_ids| {
+                dealer_ids
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, dealer_id)| (dealer_id, index + 1))
+            })
+            .collect()
+    }
+
+    fn dealers_csv(&self) -> Result<String> {
+        let namesakes = self.namesakes();
+        write_csv(
+            &[
+                "first_name",
+                "middle_name",
+                "last_name",
+                "phones",
+                "archived",
+                "namesake",
+            ],
+            self.dealers.iter().map(|(dealer, _)| {
+                vec![
+                    dealer.first_name.clone(),
+                    optional(dealer.middle_name.as_deref()),
+                    dealer.last_name.clone(),
+                    format_phones(&dealer.contacts),
+                    yes_or_blank(dealer.archived),
+                    namesake(&namesakes, dealer),
+                ]
+            }),
+        )
+    }
+
+    fn addresses_csv(&self) -> Result<String> {
+        let namesakes = self.namesakes();
+        let rows = self.dealers.iter().flat_map(|(dealer, addresses)| {
+            let namesakes = &namesakes;
+            addresses.iter().map(move |address| {
+                let mut row = dealer_columns(dealer, namesakes);
+                row.extend([
+                    optional(address.description.as_deref()),
+                    optional(address.house_num.as_deref()),
+                    optional(address.street_name.as_deref()),
+                    optional(address.locality_name.as_deref()),
+                    address.city_name.clone(),
+                    address.district_name.clone(),
+                    address.pin_code.clone(),
+                    address.state.clone(),
+                    address.country.clone(),
+                ]);
+                row
+            })
+        });
+        write_csv(
+            &[
+                "dealer_first_name",
+                "dealer_last_name",
+                "dealer_namesake",
+                "dealer_phone",
+                "description",
+                "house_num",
+                "street_name",
+                "locality_name",
+                "city_name",
+                "district_name",
+                "pin_code",
+                "state",
+                "country",
+            ],
+            rows,
+        )
+    }
+
+    fn prices_csv(&self) -> Result<String> {
+        let products: HashMap<i64, &Product> = self
+            .products
+            .iter()
+            .map(|product| (product.product_id, product))
+            .collect();
+        let dealers: HashMap<i64, &Dealer> = self
+            .dealers
+            .iter()
+            .map(|(dealer, _)| (dealer.dealer_id, dealer))
+            .collect();
+        let namesakes = self.namesakes();
+
+        write_csv(
+            &[
+                "brand",
+                "product",
+                "item",
+                "pack",
+                "dealer_first_name",
+                "dealer_last_name",
+                "dealer_namesake",
+                "dealer_phone",
+                "price",
+                "recorded_at",
+            ],
+            self.prices.iter().map(|price| {
+                let product = products[&price.product_id];
+                let mut row = vec![
+                    product.brand_name.clone(),
+                    product.product_name.clone(),
+                    product.item_name.clone(),
+                    product.pack_name.clone(),
+                ];
+                row.extend(dealer_columns(dealers[&price.dealer_id], &namesakes));
+                row.extend([plain_rupees(price.price), price.recorded_at.clone()]);
+                row
+            }),
+        )
+    }
+
+    fn json(&self) -> Value {
+        let categories: Vec<Value> = self
+            .categories
+            .iter()
+            .map(|category| {
+                json!({
+                    "category_id": category.category_id,
+                    "name": category.name,
+                    "parent_id": category.parent_id,
+                })
+            })
+            .collect();
+
+        let products: Vec<Value> = self
+            .products
+            .iter()
+            .map(|product| {
+                json!({
+                    "product_id": product.product_id,
+                    "product": product.product_name,
+                    "brand": product.brand_name,
+                    "item": product.item_name,
+                    "pack": product.pack_name,
+                    "category_id": product.category_id,
+                    "archived": product.archived,
+                })
+            })
+            .collect();
+
+        let dealers: Vec<Value> = self
+            .dealers
+            .iter()
+            .map(|(dealer, addresses)| {
+                let contacts: Vec<Value> = dealer
+                    .contacts
+                    .iter()
+                    .map(|contact| {
+                        json!({
+                            "phone": contact.e164(),
+                            "label": contact.label,
+                            "primary": contact.is_primary,
+                        })
+                    })
+                    .collect();
+                let addresses: Vec<Value> = addresses
+                    .iter()
+                    .map(|address| {
+                        json!({
+                            "description": address.description,
+                            "house_num": address.house_num,
+                            "street_name": address.street_name,
+                            "locality_name": address.locality_name,
+                            "city_name": address.city_name,
+                            "district_name": address.district_name,
+                            "pin_code": address.pin_code,
+                            "state": address.state,
+                            "country": address.country,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "dealer_id": dealer.dealer_id,
+                    "first_name": dealer.first_name,
+                    "middle_name": dealer.middle_name,
+                    "last_name": dealer.last_name,
+                    "archived": dealer.archived,
+                    "contacts": contacts,
+                    "addresses": addresses,
+                })
+            })
+            .collect();
+
+        let prices: Vec<Value> = self
+            .prices
+            .iter()
+            .map(|price| {
+                json!({
+                    "product_id": price.product_id,
+                    "dealer_id": price.dealer_id,
+                    "price_paise": price.price.paise(),
+                    "recorded_at": price.recorded_at,
+                })
+            })
+            .collect();
+
+        json!({
+            "categories": categories,
+            "products": products,
+            "dealers": dealers,
+            "prices": prices,
+        })
+    }
+}
+
+/// `dealer_first_name, dealer_last_name, dealer_namesake, dealer_phone` for
+/// finding the dealer again on import, by their primary number when they
+/// have one.
+fn dealer_columns(dealer: &Dealer, namesakes: &HashMap<i64, usize>) -> Vec<String> {
+    vec![
+        dealer.first_name.clone(),
+        dealer.last_name.clone(),
+        namesake(namesakes, dealer),
+        optional(dealer.primary_contact().map(Contact::e164).as_deref()),
+    ]
+}
+
+/// Blank for a dealer nobody shares a name with.
+fn namesake(namesakes: &HashMap<i64, usize>, dealer: &Dealer) -> String {
+    namesakes
+        .get(&dealer.dealer_id)
+        .map(usize::to_string)
+        .unwrap_or_default()
+}
+
+/// "+919876543210 (shop); +912223456789", primary first, as
+/// `import::parse_phones` reads it.
+fn format_phones(contacts: &[Contact]) -> String {
+    contacts
+        .iter()
+        .map(|contact| match &contact.label {
+            Some(label) => format!("{} ({label})", contact.e164()),
+            None => contact.e164(),
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// "1234.50", which spreadsheets read as a number.
+fn plain_rupees(price: Money) -> String {
+    format!("{}.{:02}", price.paise() / 100, price.paise() % 100)
+}
+
+fn optional(value: Option<&str>) -> String {
+    value.unwrap_or_default().to_string()
+}
+
+fn yes_or_blank(flag: bool) -> String {
+    if flag { "yes" } else { "" }.to_string()
+}
+
+fn write_csv(headers: &[&str], rows: impl Iterator<Item = Vec<String>>) -> Result<String> {
+    let mut writer = Writer::from_writer(Vec::new());
+    writer.write_record(headers).map_err(io::Error::from)?;
+    for row in rows {
+        writer.write_record(&row).map_err(io::Error::from)?;
+    }
+    let bytes = writer.into_inner().map_err(|error| error.into_error())?;
+    Ok(String::from_utf8(bytes).expect("the rows are all strings"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::{csv_headers, ColumnMapping, ImportKind};
+    use std::path::Path;
+
+    fn memory_store() -> Store {
+        Store::build(Path::new(":memory:")).unwrap()
+    }
+
+    /// A little of everything, with the awkward cases: a nested category, an
+    /// archived product, an archived dealer with no number and a comma in a
+    /// name.
+    fn filled_store() -> Store {
+        let mut store = memory_store();
+        let grocery = store.add_category("Grocery", None).unwrap();
+        let dairy = store.add_category("Dairy", Some(grocery)).unwrap();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", Some(dairy))
+            .unwrap();
+        let ghee = store
+            .add_product("Pure Ghee", "Amul", "Ghee", "1L", None)
+            .unwrap();
+        store.archive_product(ghee).unwrap();
+
+        let ramesh = store
+            .add_dealer(
+                "Ramesh",
+                Some("Kumar"),
+                "Gupta",
+                &[
+                    Contact {
+                        phone_number: "98765 43210".to_string(),
+                        label: Some("shop".to_string()),
+                        is_primary: true,
+                        ..Default::default()
+                    },
+                    Contact {
+                        phone_number: "+44 20 7946 0958".to_string(),
+                        ..Default::default()
+                    },
+                ],
+            )
+            .unwrap();
+        let suresh = store.add_dealer("Suresh", None, "Jain, Sons", &[]).unwrap();
+        store.archive_dealer(suresh).unwrap();
+        store
+            .add_dealer_address(
+                ramesh,
+                &Address {
+                    description: Some("godown".to_string()),
+                    street_name: Some("MG Road".to_string()),
+                    city_name: "Pune".to_string(),
+                    district_name: "Pune".to_string(),
+                    pin_code: "411001".to_string(),
+                    state: "Maharashtra".to_string(),
+                    country: "India".to_string(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        store
+            .update_price_at(
+                butter,
+                ramesh,
+                Money::from_paise(25050),
+                "2024-03-01 09:30:00",
+            )
+            .unwrap();
+        store
+            .update_price_at(butter, ramesh, Money::from_rupees(1260), "2024-04-01")
+            .unwrap();
+        store
+            .update_price_at(ghee, suresh, Money::from_rupees(600), "2024-04-02")
+            .unwrap();
+        store
+    }
+
+    fn export_all(store: &Store) -> Vec<(ImportKind, String)> {
+        vec![
+            (
+                ImportKind::Categories,
+                store.export_categories_csv().unwrap(),
+            ),
+            (ImportKind::Products, store.export_products_csv().unwrap()),
+            (ImportKind::Dealers, store.export_dealers_csv().unwrap()),
+            (ImportKind::Addresses, store.export_addresses_csv().unwrap()),
+            (ImportKind::Prices, store.export_prices_csv().unwrap()),
+        ]
+    }
+
+    #[test]
+    fn exported_csv_imports_into_an_empty_store() {
+        let store = filled_store();
+        let exported = export_all(&store);
+
+        let mut copy = memory_store();
+        for (kind, csv) in &exported {
+            let mapping = ColumnMapping::from_headers(*kind, &csv_headers(csv).unwrap());
+            let report = copy.import_csv(csv, &mapping).unwrap();
+            assert_eq!(report.new_rows(), report.rows.len(), "{kind:?}: {report}");
+        }
+
+        for ((kind, original), (_, copied)) in exported.iter().zip(export_all(&copy)) {
+            assert_eq!(original, &copied, "{kind:?}");
+        }
+
+        // Importing the same files again finds nothing new.
+        for (kind, csv) in &exported {
+            let mapping = ColumnMapping::from_headers(*kind, &csv_headers(csv).unwrap());
+            let report = copy.preview_import(csv, &mapping).unwrap();
+            assert_eq!(report.new_rows(), 0, "{kind:?}: {report}");
+        }
+    }
+
+    #[test]
+    fn exported_json_imports_into_an_empty_store() {
+        let store = filled_store();
+        let json = store.export_json().unwrap();
+
+        let mut copy = memory_store();
+        let report = copy.import_json(&json).unwrap();
+        assert_eq!(report.new_rows(), report.rows.len(), "{report}");
+        for ((kind, original), (_, copied)) in export_all(&store).iter().zip(export_all(&copy)) {
+            assert_eq!(original, &copied, "{kind:?}");
+        }
+
+        // Importing the same document again finds nothing new.
+        let report = copy.preview_json_import(&json).unwrap();
+        assert_eq!(report.new_rows(), 0, "{report}");
+
+        // A price for a product the document does not list imports nothing.
+        let mut document: Value = serde_json::from_str(&json).unwrap();
+        document["prices"][0]["product_id"] = json!(99);
+        let error = memory_store()
+            .import_json(&document.to_string())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Price 1: Product 99 is missing from the file or invalid"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn namesakes_without_phones_keep_their_own_records() {
+        let mut store = memory_store();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let address_in = |city: &str| Address {
+            city_name: city.to_string(),
+            district_name: city.to_string(),
+            pin_code: "411001".to_string(),
+            state: "Maharashtra".to_string(),
+            country: "India".to_string(),
+            ..Default::default()
+        };
+        for (rupees, city) in [(250, "Pune"), (260, "Nashik")] {
+            let mohan = store.add_dealer("Mohan", None, "Lal", &[]).unwrap();
+            store.add_dealer_address(mohan, &address_in(city)).unwrap();
+            store
+                .update_price_at(butter, mohan, Money::from_rupees(rupees), "2024-03-01")
+                .unwrap();
+        }
+        let exported = export_all(&store);
+        assert!(exported[2].1.contains("Mohan,,Lal,,,2\n"));
+
+        let mut copy = memory_store();
+        for (kind, csv) in &exported {
+            let mapping = ColumnMapping::from_headers(*kind, &csv_headers(csv).unwrap());
+            let report = copy.import_csv(csv, &mapping).unwrap();
+            assert_eq!(report.new_rows(), report.rows.len(), "{kind:?}: {report}");
+        }
+        for ((kind, original), (_, copied)) in exported.iter().zip(export_all(&copy)) {
+            assert_eq!(original, &copied, "{kind:?}");
+        }
+
+        let second = copy.get_dealers().unwrap()[1].dealer_id;
+        assert_eq!(
+            copy.get_dealer_addresses(second).unwrap()[0].city_name,
+            "Nashik"
+        );
+        assert_eq!(
+            copy.get_price_history(butter, second).unwrap()[0].price,
+            Money::from_rupees(260)
+        );
+    }
+
+    #[test]
+    fn csv_and_json_hold_the_whole_store() {
+        let store = filled_store();
+        assert_eq!(
+            store.export_prices_csv().unwrap(),
+            "\
+brand,product,item,pack,dealer_first_name,dealer_last_name,dealer_namesake,dealer_phone,price,recorded_at
+Amul,Butter,Butter,500g,Ramesh,Gupta,,+919876543210,250.50,2024-03-01 09:30:00
+Amul,Butter,Butter,500g,Ramesh,Gupta,,+919876543210,1260.00,2024-04-01 00:00:00
+Amul,Pure Ghee,Ghee,1L,Suresh,\"Jain, Sons\",,,600.00,2024-04-02 00:00:00
+"
+        );
+        assert!(store
+            .export_products_csv()
+            .unwrap()
+            .contains("Butter,Amul,Butter,500g,Grocery > Dairy,"));
+        assert!(store
+            .export_dealers_csv()
+            .unwrap()
+            .contains("Ramesh,Kumar,Gupta,+919876543210 (shop); +442079460958,"));
+
+        let json: Value = serde_json::from_str(&store.export_json().unwrap()).unwrap();
+        assert_eq!(json["categories"][1]["name"], "Dairy");
+        assert_eq!(json["products"][1]["archived"], true);
+        assert_eq!(json["dealers"][0]["contacts"][0]["label"], "shop");
+        assert_eq!(json["dealers"][0]["addresses"][0]["pin_code"], "411001");
+        assert_eq!(json["prices"].as_array().unwrap().len(), 3);
+        assert_eq!(json["prices"][0]["price_paise"], 25050);
+    }
+
+    #[test]
+    fn export_writes_every_file() {
+        let store = filled_store();
+        let mut directory = std::env::temp_dir();
+        directory.push(format!("gsm-test-{}-export", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+
+        store.export_to(&directory).unwrap();
+        assert_eq!(
+            fs::read_to_string(directory.join("products.csv")).unwrap(),
+            store.export_products_csv().unwrap()
+        );
+        for name in [
+            "categories.csv",
+            "dealers.csv",
+            "addresses.csv",
+            "prices.csv",
+            "store.json",
+        ] {
+            assert!(directory.join(name).is_file(), "{name}");
+        }
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,1384 @@
+use crate::address::{self, Address};
+use crate::archive;
+use crate::category;
+use crate::contact::{self, Contact};
+use crate::{
+    insert_dealer, insert_product, price, required, tidy_name, Money, Result, Store, StoreError,
+};
+use csv::{ReaderBuilder, StringRecord, Trim};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+/// What the rows of a CSV file describe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportKind {
+    /// One product per row, added like `add_product`.
+    Products,
+    /// One dealer per row, added like `add_dealer`.
+    Dealers,
+    /// One price per row, for a product and a dealer already in the store.
+    Prices,
+    /// One address per row, for a dealer already in the store, added like
+    /// `add_dealer_address`.
+    Addresses,
+    /// One category per row, added like `add_category`. Parents must come
+    /// before their children.
+    ///
+    /// Categories, here and in the `Products` category column, are named by
+    /// their path, like "Grocery > Oil", because only siblings need different
+    /// names. A bare name will do while only one category has it.
+    Categories,
+}
+
+/// A value an import reads from each row.
+#[derive(Debug)]
+pub struct ImportField {
+    pub name: &'static str,
+    pub required: bool,
+    /// Headers the field is recognised by, lowercase and without spaces,
+    /// underscores or other punctuation.
+    headers: &'static [&'static str],
+}
+
+const fn field(
+    name: &'static str,
+    required: bool,
+    headers: &'static [&'static str],
+) -> ImportField {
+    ImportField {
+        name,
+        required,
+        headers,
+    }
+}
+
+const PRODUCT_FIELDS: &[ImportField] = &[
+    field("product", true, &["product", "productname", "name"]),
+    field("brand", true, &["brand", "brandname"]),
+    field("item", true, &["item", "itemname"]),
+    field("pack", true, &["pack", "packname", "packsize", "size"]),
+    field("category", false, &["category", "categoryname"]),
+    field("archived", false, &["archived"]),
+];
+
+const DEALER_FIELDS: &[ImportField] = &[
+    field("first_name", true, &["firstname", "first"]),
+    field("middle_name", false, &["middlename", "middle"]),
+    field("last_name", true, &["lastname", "last", "surname"]),
+    field(
+        "phones",
+        false,
+        &["phones", "phone", "phonenumbers", "phonenumber", "mobile"],
+    ),
+    field("archived", false, &["archived"]),
+    field("namesake", false, &["namesake"]),
+];
+
+/// The dealer is found by `dealer_phone` when there is one, and otherwise
+/// by name, so neither is required on its own. Dealers sharing a name are
+/// told apart by `dealer_namesake`, as `DEALER_FIELDS` numbers them.
+const PRICE_FIELDS: &[ImportField] = &[
+    field("brand", true, &["brand", "brandname"]),
+    field("product", true, &["product", "productname"]),
+    field("pack", true, &["pack", "packname", "packsize", "size"]),
+    field("item", false, &["item", "itemname"]),
+    field("dealer_first_name", false, &["dealerfirstname"]),
+    field("dealer_last_name", false, &["dealerlastname"]),
+    field("dealer_namesake", false, &["dealernamesake"]),
+    field("dealer_phone", false, &["dealerphone", "phone"]),
+    field("price", true, &["price", "rate"]),
+    field(
+        "recorded_at",
+        false,
+        &["recordedat", "date", "timestamp", "time"],
+    ),
+];
+
+/// The dealer is found like in `PRICE_FIELDS`.
+const ADDRESS_FIELDS: &[ImportField] = &[
+    field("dealer_first_name", false, &["dealerfirstname"]),
+    field("dealer_last_name", false, &["dealerlastname"]),
+    field("dealer_namesake", false, &["dealernamesake"]),
+    field("dealer_phone", false, &["dealerphone", "phone"]),
+    field("description", false, &["description"]),
+    field("house_num", false, &["housenum", "housenumber", "house"]),
+    field("street_name", false, &["streetname", "street"]),
+    field(
+        "locality_name",
+        false,
+        &["localityname", "locality", "area"],
+    ),
+    field("city_name", true, &["cityname", "city"]),
+    field("district_name", true, &["districtname", "district"]),
+    field("pin_code", true, &["pincode", "pin", "postcode"]),
+    field("state", true, &["state"]),
+    field("country", true, &["country"]),
+];
+
+const CATEGORY_FIELDS: &[ImportField] = &[
+    field("name", true, &["name", "category", "categoryname"]),
+    field("parent", false, &["parent", "parentname", "parentcategory"]),
+];
+
+impl ImportKind {
+    /// The fields rows of this kind are read from.
+    pub fn fields(self) -> &'static [ImportField] {
+        match self {
+            ImportKind::Products => PRODUCT_FIELDS,
+            ImportKind::Dealers => DEALER_FIELDS,
+            ImportKind::Prices => PRICE_FIELDS,
+            ImportKind::Addresses => ADDRESS_FIELDS,
+            ImportKind::Categories => CATEGORY_FIELDS,
+        }
+    }
+}
+
+/// Which CSV column each field of an import is read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMapping {
+    kind: ImportKind,
+    /// Indexed like `kind.fields()`.
+    columns: Vec<Option<usize>>,
+}
+
+impl ColumnMapping {
+    /// A mapping with no columns chosen yet.
+    pub fn new(kind: ImportKind) -> Self {
+        ColumnMapping {
+            kind,
+            columns: vec![None; kind.fields().len()],
+        }
+    }
+
+    /// Maps each field to the first header that names it, ignoring case,
+    /// spaces and punctuation, so "Brand Name" and "brand_name" both find
+    /// `brand`.
+    pub fn from_headers(kind: ImportKind, headers: &[String]) -> Self {
+        let headers: Vec<String> = headers.iter().map(|header| header_key(header)).collect();
+        let columns = kind
+            .fields()
+            .iter()
+            .map(|field| {
+                headers
+                    .iter()
+                    .position(|header| field.headers.contains(&header.as_str()))
+            })
+            .collect();
+        ColumnMapping { kind, columns }
+    }
+
+    pub fn kind(&self) -> ImportKind {
+        self.kind
+    }
+
+    /// The column `field` is read from.
+    pub fn column(&self, field: &str) -> Option<usize> {
+        self.columns[self.index_of(field)?]
+    }
+
+    /// Reads `field` from `column`, or from nowhere when `None`.
+    pub fn set(&mut self, field: &str, column: Option<usize>) -> Result<()> {
+        let index = self.index_of(field).ok_or_else(|| {
+            StoreError::validation(format!("There is no \"{field}\" field to import"))
+        })?;
+        self.columns[index] = column;
+        Ok(())
+    }
+
+    /// Required fields that have no column.
+    pub fn missing(&self) -> Vec<&'static str> {
+        self.kind
+            .fields()
+            .iter()
+            .zip(&self.columns)
+            .filter(|(field, column)| field.required && column.is_none())
+            .map(|(field, _)| field.name)
+            .collect()
+    }
+
+    fn index_of(&self, field: &str) -> Option<usize> {
+        self.kind
+            .fields()
+            .iter()
+            .position(|known| known.name == field)
+    }
+}
+
+/// What importing a row did, or would do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowOutcome {
+    New,
+    /// Already in the store, or earlier in the file, and skipped. The
+    /// message says what it matched.
+    Duplicate(String),
+    /// The message says what is wrong with the row.
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    /// The row's line in the file, counting the header as line 1, or for
+    /// `import_json` the record's place in the document, counting from 1.
+    pub line: u64,
+    pub outcome: RowOutcome,
+}
+
+/// What happened to each row of a file. Blank rows are left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    pub rows: Vec<ImportRow>,
+}
+
+impl ImportReport {
+    pub fn new_rows(&self) -> usize {
+        self.count(|outcome| matches!(outcome, RowOutcome::New))
+    }
+
+    pub fn duplicates(&self) -> usize {
+        self.count(|outcome| matches!(outcome, RowOutcome::Duplicate(_)))
+    }
+
+    pub fn invalid(&self) -> usize {
+        self.count(|outcome| matches!(outcome, RowOutcome::Invalid(_)))
+    }
+
+    fn count(&self, matches: impl Fn(&RowOutcome) -> bool) -> usize {
+        self.rows.iter().filter(|row| matches(&row.outcome)).count()
+    }
+}
+
+/// "12 new, 3 duplicate, 1 invalid"
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} new, {} duplicate, {} invalid",
+            self.new_rows(),
+            self.duplicates(),
+            self.invalid()
+        )
+    }
+}
+
+/// The header row of `csv`, for choosing a `ColumnMapping`.
+pub fn csv_headers(csv: &str) -> Result<Vec<String>> {
+    let headers = reader(csv).headers().map_err(invalid_csv)?.clone();
+    Ok(headers.iter().map(str::to_string).collect())
+}
+
+impl Store {
+    /// Reports what `import_csv` would do with each row without changing
+    /// anything.
+    pub fn preview_import(&mut self, csv: &str, mapping: &ColumnMapping) -> Result<ImportReport> {
+        self.run_import(csv, mapping, false)
+    }
+
+    /// Adds every new row of `csv`, read through `mapping`, and skips
+    /// duplicates. Nothing is imported if any row is invalid, so the file
+    /// can be fixed and imported again.
+    pub fn import_csv(&mut self, csv: &str, mapping: &ColumnMapping) -> Result<ImportReport> {
+        self.run_import(csv, mapping, true)
+    }
+
+    /// Imports every row in one transaction, each inside a savepoint so that
+    /// an invalid row leaves nothing half-written, and keeps the transaction
+    /// only when asked to and every row was valid.
+    fn run_import(
+        &mut self,
+        csv: &str,
+        mapping: &ColumnMapping,
+        keep: bool,
+    ) -> Result<ImportReport> {
+        let missing = mapping.missing();
+        if !missing.is_empty() {
+            return Err(StoreError::validation(format!(
+                "Choose a column for {}",
+                missing.join(", ")
+            )));
+        }
+
+        let mut reader = reader(csv);
+        let mut transaction = self.connection.transaction()?;
+        let mut report = ImportReport::default();
+
+        for record in reader.records() {
+            let record = record.map_err(invalid_csv)?;
+            if record.iter().all(str::is_empty) {
+                continue;
+            }
+            // The reader skips blank lines without counting them, and the row's
+            // position is from before them, so count lines up to the row's text.
+            let start = record
+                .position()
+                .map_or(0, |position| position.byte() as usize);
+            let rest = &csv[start..];
+            let text_start = start + rest.len() - rest.trim_start_matches(['\r', '\n']).len();
+            let line = csv[..text_start].matches('\n').count() as u64 + 1;
+            let fields = Fields {
+                record: &record,
+                mapping,
+            };
+
+            let outcome = in_savepoint(&mut transaction, |connection| {
+                import_row(connection, mapping.kind, &fields)
+            })?
+            .unwrap_or_else(RowOutcome::Invalid);
+            report.rows.push(ImportRow { line, outcome });
+        }
+
+        if keep {
+            commit_if_valid(transaction, &report, "line")?;
+        }
+        Ok(report)
+    }
+
+    /// Reports what `import_json` would do with each record without changing
+    /// anything.
+    pub fn preview_json_import(&mut self, json: &str) -> Result<ImportReport> {
+        self.run_json_import(json, false)
+    }
+
+    /// Adds every new record of a document written by `export_json` and
+    /// skips duplicates. Records refer to each other by the ids of the store
+    /// that wrote the document, which are matched to the records they are
+    /// here. Nothing is imported if any record is invalid.
+    ///
+    /// The report numbers records in the order they are read: categories,
+    /// products, each dealer followed by their addresses, then prices.
+    pub fn import_json(&mut self, json: &str) -> Result<ImportReport> {
+        self.run_json_import(json, true)
+    }
+
+    fn run_json_import(&mut self, json: &str, keep: bool) -> Result<ImportReport> {
+        let document: Value = serde_json::from_str(json).map_err(|error| {
+            StoreError::validation(format!("The file is not valid JSON: {error}"))
+        })?;
+        let mut transaction = self.connection.transaction()?;
+        let mut report = ImportReport::default();
+        // The ids the document's records have in this store.
+        let mut categories = HashMap::new();
+        let mut products = HashMap::new();
+        let mut dealers = HashMap::new();
+
+        for record in section(&document, "categories")? {
+            let label = record.label("Category", "category_id");
+            let category_id = import_record(&mut transaction, &mut report, &label, |connection| {
+                let parent_id = record
+                    .integer("parent_id")?
+                    .map(|parent_id| mapped(&categories, "Category", parent_id))
+                    .transpose()?;
+                add_category_row(connection, record.text("name")?, parent_id)
+            })?;
+            if let (Ok(Some(document_id)), Some(category_id)) =
+                (record.integer("category_id"), category_id)
+            {
+                categories.insert(document_id, category_id);
+            }
+        }
+
+        for record in section(&document, "products")? {
+            let label = record.label("Product", "product_id");
+            let product_id = import_record(&mut transaction, &mut report, &label, |connection| {
+                let category_id = record
+                    .integer("category_id")?
+                    .map(|category_id| mapped(&categories, "Category", category_id))
+                    .transpose()?;
+                add_product_row(
+                    connection,
+                    record.text("product")?,
+                    record.text("brand")?,
+                    record.text("item")?,
+                    record.text("pack")?,
+                    category_id,
+                    record.flag("archived")?,
+                )
+            })?;
+            if let (Ok(Some(document_id)), Some(product_id)) =
+                (record.integer("product_id"), product_id)
+            {
+                products.insert(document_id, product_id);
+            }
+        }
+
+        // Dealers sharing a name are numbered in the order the document
+        // lists them, as the dealers CSV numbers them.
+        let mut namesakes: HashMap<(String, String), usize> = HashMap::new();
+        for record in section(&document, "dealers")? {
+            let label = record.label("Dealer", "dealer_id");
+            let name = |field| {
+                record
+                    .text(field)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_ascii_lowercase()
+            };
+            let namesake = namesakes
+                .entry((name("first_name"), name("last_name")))
+                .or_default();
+            *namesake += 1;
+            let namesake = *namesake;
+
+            let dealer_id = import_record(&mut transaction, &mut report, &label, |connection| {
+                let mut contacts = Vec::new();
+                for contact in record.records("contacts")? {
+                    let (country_code, phone_number) =
+                        contact::normalize_phone("", contact.text("phone")?)?;
+                    contacts.push(Contact {
+                        country_code,
+                        phone_number,
+                        label: contact.optional_text("label")?.map(str::to_string),
+                        is_primary: contact.flag("primary")?,
+                        ..Default::default()
+                    });
+                }
+                add_dealer_row(
+                    connection,
+                    record.text("first_name")?,
+                    record.optional_text("middle_name")?,
+                    record.text("last_name")?,
+                    &contacts,
+                    record.flag("archived")?,
+                    namesake,
+                )
+            })?;
+            if let (Ok(Some(document_id)), Some(dealer_id)) =
+                (record.integer("dealer_id"), dealer_id)
+            {
+                dealers.insert(document_id, dealer_id);
+            }
+
+            for (number, address) in record.records("addresses")?.iter().enumerate() {
+                let label = format!("Address {} of {}", number + 1, label.to_lowercase());
+                import_record(&mut transaction, &mut report, &label, |connection| {
+                    let dealer_id =
+                        dealer_id.ok_or_else(|| StoreError::validation("The dealer is invalid"))?;
+                    let optional = |field| -> Result<Option<String>> {
+                        Ok(address.optional_text(field)?.map(str::to_string))
+                    };
+                    let address = Address {
+                        description: optional("description")?,
+                        house_num: optional("house_num")?,
+                        street_name: optional("street_name")?,
+                        locality_name: optional("locality_name")?,
+                        city_name: address.text("city_name")?.to_string(),
+                        district_name: address.text("district_name")?.to_string(),
+                        pin_code: address.text("pin_code")?.to_string(),
+                        state: address.text("state")?.to_string(),
+                        country: address.text("country")?.to_string(),
+                        ..Default::default()
+                    };
+                    Ok((add_address_row(connection, dealer_id, address)?, ()))
+                })?;
+            }
+        }
+
+        for (number, record) in section(&document, "prices")?.iter().enumerate() {
+            let label = format!("Price {}", number + 1);
+            import_record(&mut transaction, &mut report, &label, |connection| {
+                let product_id =
+                    mapped(&products, "Product", record.required_integer("product_id")?)?;
+                let dealer_id = mapped(&dealers, "Dealer", record.required_integer("dealer_id")?)?;
+                let price = Money::from_paise(record.required_integer("price_paise")?);
+                let recorded_at = record.optional_text("recorded_at")?.unwrap_or("now");
+                Ok((
+                    add_price_row(connection, product_id, dealer_id, price, recorded_at)?,
+                    (),
+                ))
+            })?;
+        }
+
+        if keep {
+            commit_if_valid(transaction, &report, "record")?;
+        }
+        Ok(report)
+    }
+}
+
+/// Runs `import` inside a savepoint, keeping what it wrote only when it
+/// succeeds, and hands back a record the user can fix as its message
+/// rather than as an error.
+fn in_savepoint<T>(
+    transaction: &mut Transaction,
+    import: impl FnOnce(&Connection) -> Result<T>,
+) -> Result<std::result::Result<T, String>> {
+    let savepoint = transaction.savepoint()?;
+    match import(&savepoint) {
+        Ok(imported) => {
+            savepoint.commit()?;
+            Ok(Ok(imported))
+        }
+        Err(StoreError::Validation(message)) => Ok(Err(message)),
+        Err(error @ StoreError::NotFound(_)) => Ok(Err(error.to_string())),
+        Err(error) => Err(error),
+    }
+}
+
+/// Keeps an import only when every row of `report` was valid. `place` says
+/// what the report numbers, like "line".
+fn commit_if_valid(transaction: Transaction, report: &ImportReport, place: &str) -> Result<()> {
+    let first_invalid = report.rows.iter().find_map(|row| match &row.outcome {
+        RowOutcome::Invalid(message) => Some((row.line, message)),
+        _ => None,
+    });
+    if let Some((line, message)) = first_invalid {
+        return Err(StoreError::validation(format!(
+            "Nothing was imported: {place} {line} is invalid ({message}) and {} more",
+            report.invalid() - 1
+        )));
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Imports one record and reports it under `label`, handing back its id in
+/// this store unless it was invalid.
+fn import_record<T>(
+    transaction: &mut Transaction,
+    report: &mut ImportReport,
+    label: &str,
+    import: impl FnOnce(&Connection) -> Result<(RowOutcome, T)>,
+) -> Result<Option<T>> {
+    let (outcome, imported) = match in_savepoint(transaction, import)? {
+        Ok((outcome, imported)) => (outcome, Some(imported)),
+        Err(message) => (RowOutcome::Invalid(format!("{label}: {message}")), None),
+    };
+    report.rows.push(ImportRow {
+        line: report.rows.len() as u64 + 1,
+        outcome,
+    });
+    Ok(imported)
+}
+
+/// The id in this store of the record the document gave `document_id`.
+fn mapped(ids: &HashMap<i64, i64>, what: &str, document_id: i64) -> Result<i64> {
+    ids.get(&document_id).copied().ok_or_else(|| {
+        StoreError::validation(format!(
+            "{what} {document_id} is missing from the file or invalid"
+        ))
+    })
+}
+
+/// The objects listed under `name`, or none when the document leaves it out.
+fn section<'a>(document: &'a Value, name: &str) -> Result<Vec<JsonRecord<'a>>> {
+    match document.get(name) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(value) => JsonRecord::list(name, value),
+    }
+}
+
+/// One object of a JSON document, read field by field. Missing fields and
+/// nulls read as blank.
+struct JsonRecord<'a>(&'a Map<String, Value>);
+
+impl<'a> JsonRecord<'a> {
+    fn list(name: &str, value: &'a Value) -> Result<Vec<Self>> {
+        let not_a_list =
+            || StoreError::validation(format!("\"{name}\" should be a list of objects"));
+        value
+            .as_array()
+            .ok_or_else(not_a_list)?
+            .iter()
+            .map(|item| item.as_object().map(JsonRecord).ok_or_else(not_a_list))
+            .collect()
+    }
+
+    fn value(&self, field: &str) -> Option<&'a Value> {
+        self.0.get(field).filter(|value| !value.is_null())
+    }
+
+    /// "Dealer 4", by the id the document gave the record.
+    fn label(&self, kind: &str, id_field: &str) -> String {
+        match self.integer(id_field) {
+            Ok(Some(id)) => format!("{kind} {id}"),
+            _ => kind.to_string(),
+        }
+    }
+
+    fn text(&self, field: &str) -> Result<&'a str> {
+        match self.value(field) {
+            None => Ok(""),
+            Some(Value::String(text)) => Ok(text),
+            Some(value) => Err(StoreError::validation(format!(
+                "{field} should be text, not {value}"
+            ))),
+        }
+    }
+
+    fn optional_text(&self, field: &str) -> Result<Option<&'a str>> {
+        Ok(Some(self.text(field)?).filter(|text| !text.trim().is_empty()))
+    }
+
+    fn integer(&self, field: &str) -> Result<Option<i64>> {
+        self.value(field)
+            .map(|value| {
+                value.as_i64().ok_or_else(|| {
+                    StoreError::validation(format!("{field} should be a whole number, not {value}"))
+                })
+            })
+            .transpose()
+    }
+
+    fn required_integer(&self, field: &str) -> Result<i64> {
+        self.integer(field)?
+            .ok_or_else(|| StoreError::validation(format!("{field} is missing")))
+    }
+
+    fn flag(&self, field: &str) -> Result<bool> {
+        match self.value(field) {
+            None => Ok(false),
+            Some(Value::Bool(flag)) => Ok(*flag),
+            Some(value) => Err(StoreError::validation(format!(
+                "{field} should be true or false, not {value}"
+            ))),
+        }
+    }
+
+    fn records(&self, field: &str) -> Result<Vec<JsonRecord<'a>>> {
+        match self.value(field) {
+            None => Ok(Vec::new()),
+            Some(value) => Self::list(field, value),
+        }
+    }
+}
+
+/// One row's values, looked up by field name.
+struct Fields<'a> {
+    record: &'a StringRecord,
+    mapping: &'a ColumnMapping,
+}
+
+impl Fields<'_> {
+    /// Blank when the field has no column or the row is too short for it.
+    fn get(&self, field: &str) -> &str {
+        self.mapping
+            .column(field)
+            .and_then(|column| self.record.get(column))
+            .unwrap_or("")
+    }
+
+    fn optional(&self, field: &str) -> Option<&str> {
+        Some(self.get(field)).filter(|value| !value.is_empty())
+    }
+
+    /// "yes", "true", "y" or "1" in any case, and no when blank.
+    fn flag(&self, field: &str) -> Result<bool> {
+        match self.get(field).to_lowercase().as_str() {
+            "yes" | "true" | "y" | "1" => Ok(true),
+            "" | "no" | "false" | "n" | "0" => Ok(false),
+            value => Err(StoreError::validation(format!(
+                "\"{value}\" is not yes or no"
+            ))),
+        }
+    }
+
+    /// Which of the dealers sharing a name the row means, counting from 1
+    /// in the order they were added.
+    fn namesake(&self, field: &str) -> Result<Option<usize>> {
+        let Some(value) = self.optional(field) else {
+            return Ok(None);
+        };
+        match value.parse() {
+            Ok(namesake) if namesake > 0 => Ok(Some(namesake)),
+            _ => Err(StoreError::validation(format!(
+                "\"{value}\" is not a namesake number"
+            ))),
+        }
+    }
+}
+
+fn import_row(connection: &Connection, kind: ImportKind, fields: &Fields) -> Result<RowOutcome> {
+    match kind {
+        ImportKind::Products => import_product(connection, fields),
+        ImportKind::Dealers => import_dealer(connection, fields),
+        ImportKind::Prices => import_price(connection, fields),
+        ImportKind::Addresses => import_address(connection, fields),
+        ImportKind::Categories => import_category(connection, fields),
+    }
+}
+
+fn import_product(connection: &Connection, fields: &Fields) -> Result<RowOutcome> {
+    let category_id = fields
+        .optional("category")
+        .map(|category| category_id_for(connection, category))
+        .transpose()?;
+    let (outcome, _) = add_product_row(
+        connection,
+        fields.get("product"),
+        fields.get("brand"),
+        fields.get("item"),
+        fields.get("pack"),
+        category_id,
+        fields.flag("archived")?,
+    )?;
+    Ok(outcome)
+}
+
+fn import_dealer(connection: &Connection, fields: &Fields) -> Result<RowOutcome> {
+    let (outcome, _) = add_dealer_row(
+        connection,
+        fields.get("first_name"),
+        fields.optional("middle_name"),
+        fields.get("last_name"),
+        &parse_phones(fields.get("phones"))?,
+        fields.flag("archived")?,
+        fields.namesake("namesake")?.unwrap_or(1),
+    )?;
+    Ok(outcome)
+}
+
+fn import_price(connection: &Connection, fields: &Fields) -> Result<RowOutcome> {
+    let brand_name = required("Brand name", fields.get("brand"))?;
+    let product_name = required("Product name", fields.get("product"))?;
+    let pack_name = required("Pack name", fields.get("pack"))?;
+    let price: Money = required("Price", fields.get("price"))?.parse()?;
+
+    let products = find_products(
+        connection,
+        brand_name,
+        product_name,
+        pack_name,
+        fields.optional("item"),
+    )?;
+    let product_id = match products[..] {
+        [product_id] => product_id,
+        [] => {
+            return Err(StoreError::validation(format!(
+                "There is no product {brand_name} {product_name} ({pack_name})"
+            )))
+        }
+        _ => {
+            return Err(StoreError::validation(format!(
+                "Several products are called {brand_name} {product_name} ({pack_name}); \
+                 add an item column to tell them apart"
+            )))
+        }
+    };
+    let dealer_id = dealer_for(connection, fields)?;
+
+    add_price_row(
+        connection,
+        product_id,
+        dealer_id,
+        price,
+        fields.optional("recorded_at").unwrap_or("now"),
+    )
+}
+
+fn import_address(connection: &Connection, fields: &Fields) -> Result<RowOutcome> {
+    let optional = |field| fields.optional(field).map(str::to_string);
+    let address = Address {
+        description: optional("description"),
+        house_num: optional("house_num"),
+        street_name: optional("street_name"),
+        locality_name: optional("locality_name"),
+        city_name: fields.get("city_name").to_string(),
+        district_name: fields.get("district_name").to_string(),
+        pin_code: fields.get("pin_code").to_string(),
+        state: fields.get("state").to_string(),
+        country: fields.get("country").to_string(),
+        ..Default::default()
+    };
+    let dealer_id = dealer_for(connection, fields)?;
+    add_address_row(connection, dealer_id, address)
+}
+
+fn import_category(connection: &Connection, fields: &Fields) -> Result<RowOutcome> {
+    let parent_id = fields
+        .optional("parent")
+        .map(|parent| category_id_for(connection, parent))
+        .transpose()?;
+    let (outcome, _) = add_category_row(connection, fields.get("name"), parent_id)?;
+    Ok(outcome)
+}
+
+/// Adds the product like `add_product` unless the store has it already, and
+/// hands back its id either way.
+fn add_product_row(
+    connection: &Connection,
+    product_name: &str,
+    brand_name: &str,
+    item_name: &str,
+    pack_name: &str,
+    category_id: Option<i64>,
+    archived: bool,
+) -> Result<(RowOutcome, i64)> {
+    let product_name = required("Product name", product_name)?;
+    let brand_name = required("Brand name", brand_name)?;
+    let item_name = required("Item name", item_name)?;
+    let pack_name = required("Pack name", pack_name)?;
+
+    if let Some(&product_id) = find_products(
+        connection,
+        brand_name,
+        product_name,
+        pack_name,
+        Some(item_name),
+    )?
+    .first()
+    {
+        return Ok((
+            RowOutcome::Duplicate("This product is already in the store".to_string()),
+            product_id,
+        ));
+    }
+    let product_id = insert_product(
+        connection,
+        product_name,
+        brand_name,
+        item_name,
+        pack_name,
+        category_id,
+    )?;
+    archive::set_archived(connection, "product", product_id, archived)?;
+    Ok((RowOutcome::New, product_id))
+}
+
+/// Adds the dealer like `add_dealer` unless the store has them already, and
+/// hands back their id either way. `namesake` says which of the dealers
+/// sharing their name they are, counting from 1.
+fn add_dealer_row(
+    connection: &Connection,
+    first_name: &str,
+    middle_name: Option<&str>,
+    last_name: &str,
+    contacts: &[Contact],
+    archived: bool,
+    namesake: usize,
+) -> Result<(RowOutcome, i64)> {
+    let first_name = required("First name", first_name)?;
+    let last_name = required("Last name", last_name)?;
+
+    for contact in contacts {
+        if let Some(dealer_id) = dealer_with_phone(connection, contact)? {
+            return Ok((
+                RowOutcome::Duplicate(format!(
+                    "A dealer already has the number {}",
+                    contact.formatted()
+                )),
+                dealer_id,
+            ));
+        }
+    }
+    // Namesakes are told apart by their numbers, so a name alone only
+    // matches when there are none to go by. Without numbers, `namesake`
+    // says how many dealers of that name the store should have.
+    if contacts.is_empty() {
+        if let Some(&dealer_id) =
+            dealers_named(connection, first_name, last_name)?.get(namesake - 1)
+        {
+            return Ok((
+                RowOutcome::Duplicate(format!(
+                    "A dealer called {first_name} {last_name} already exists"
+                )),
+                dealer_id,
+            ));
+        }
+    }
+
+    let dealer_id = insert_dealer(connection, first_name, middle_name, last_name, contacts)?;
+    archive::set_archived(connection, "dealer", dealer_id, archived)?;
+    Ok((RowOutcome::New, dealer_id))
+}
+
+fn add_price_row(
+    connection: &Connection,
+    product_id: i64,
+    dealer_id: i64,
+    price: Money,
+    recorded_at: &str,
+) -> Result<RowOutcome> {
+    let recorded: bool = connection.query_row(
+        "
+        SELECT EXISTS (
+            SELECT 1 FROM dealer_price
+            WHERE product_id = ?1 AND dealer_id = ?2 AND time_stamp = datetime(?3) AND price_paise = ?4
+        )
+        ",
+        params![product_id, dealer_id, recorded_at, price],
+        |row| row.get(0),
+    )?;
+    if recorded {
+        return Ok(RowOutcome::Duplicate(
+            "This price is already recorded".to_string(),
+        ));
+    }
+
+    price::insert_price(connection, product_id, dealer_id, price, recorded_at)?;
+    Ok(RowOutcome::New)
+}
+
+fn add_address_row(
+    connection: &Connection,
+    dealer_id: i64,
+    address: Address,
+) -> Result<RowOutcome> {
+    let address = address.normalized()?;
+    let known: bool = connection.query_row(
+        "
+        SELECT EXISTS (
+            SELECT 1 FROM dealer_location
+            JOIN address ON address.address_id = dealer_location.address_id
+            WHERE dealer_location.dealer_id = ?1
+            AND address.house_num IS ?2 COLLATE NOCASE
+            AND address.street_name IS ?3 COLLATE NOCASE
+            AND address.locality_name IS ?4 COLLATE NOCASE
+            AND address.city_name = ?5 COLLATE NOCASE
+            AND address.pin_code = ?6
+        )
+        ",
+        params![
+            dealer_id,
+            address.house_num,
+            address.street_name,
+            address.locality_name,
+            address.city_name,
+            address.pin_code
+        ],
+        |row| row.get(0),
+    )?;
+    if known {
+        return Ok(RowOutcome::Duplicate(
+            "The dealer already has this address".to_string(),
+        ));
+    }
+
+    address::insert_address(connection, dealer_id, &address)?;
+    Ok(RowOutcome::New)
+}
+
+/// Adds the category like `add_category` unless its parent already has one
+/// of that name, and hands back its id either way.
+fn add_category_row(
+    connection: &Connection,
+    name: &str,
+    parent_id: Option<i64>,
+) -> Result<(RowOutcome, i64)> {
+    match category::insert_category(connection, name, parent_id) {
+        Ok(category_id) => Ok((RowOutcome::New, category_id)),
+        Err(StoreError::Duplicate(_)) => {
+            let category_id = connection.query_row(
+                "SELECT category_id FROM category WHERE parent_id IS ?1 AND name = ?2 COLLATE NOCASE",
+                params![parent_id, name.trim()],
+                |row| row.get(0),
+            )?;
+            Ok((
+                RowOutcome::Duplicate("This category already exists".to_string()),
+                category_id,
+            ))
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// The dealer a price or address row names, by phone number if it has one.
+fn dealer_for(connection: &Connection, fields: &Fields) -> Result<i64> {
+    if let Some(phone_number) = fields.optional("dealer_phone") {
+        let contact = Contact {
+            phone_number: phone_number.to_string(),
+            ..Default::default()
+        };
+        return dealer_with_phone(connection, &contact)?.ok_or_else(|| {
+            StoreError::validation(format!("No dealer has the number {phone_number}"))
+        });
+    }
+
+    let (Some(first_name), Some(last_name)) = (
+        fields.optional("dealer_first_name"),
+        fields.optional("dealer_last_name"),
+    ) else {
+        return Err(StoreError::validation(
+            "A dealer phone number, or a dealer first and last name, is required",
+        ));
+    };
+    let dealer_ids = dealers_named(connection, first_name, last_name)?;
+    match (&dealer_ids[..], fields.namesake("dealer_namesake")?) {
+        ([], _) => Err(StoreError::validation(format!(
+            "There is no dealer called {first_name} {last_name}"
+        ))),
+        ([dealer_id], None) => Ok(*dealer_id),
+        (_, None) => Err(StoreError::validation(format!(
+            "Several dealers are called {first_name} {last_name}; add a dealer phone or \
+             dealer namesake column to tell them apart"
+        ))),
+        (_, Some(namesake)) => dealer_ids.get(namesake - 1).copied().ok_or_else(|| {
+            StoreError::validation(format!(
+                "There is no dealer called {first_name} {last_name} with namesake number {namesake}"
+            ))
+        }),
+    }
+}
+
+/// Products with these names and pack, ignoring case, archived ones
+/// included.
+fn find_products(
+    connection: &Connection,
+    brand_name: &str,
+    product_name: &str,
+    pack_name: &str,
+    item_name: Option<&str>,
+) -> Result<Vec<i64>> {
+    let product_ids = connection
+        .prepare_cached(
+            "
+            SELECT product.product_id
+            FROM product
+            JOIN brand ON brand.brand_id = product.brand_id
+            JOIN item ON item.item_id = product.item_id
+            WHERE brand.name = ?1 COLLATE NOCASE
+            AND product.name = ?2 COLLATE NOCASE
+            AND product.pack_name = ?3 COLLATE NOCASE
+            AND (?4 IS NULL OR item.name = ?4 COLLATE NOCASE)
+            ORDER BY product.product_id
+            ",
+        )?
+        .query_map(
+            params![
+                tidy_name(brand_name),
+                product_name,
+                pack_name,
+                item_name.map(tidy_name)
+            ],
+            |row| row.get(0),
+        )?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(product_ids)
+}
+
+fn dealers_named(connection: &Connection, first_name: &str, last_name: &str) -> Result<Vec<i64>> {
+    let dealer_ids = connection
+        .prepare_cached(
+            "
+            SELECT dealer_id FROM dealer
+            WHERE first_name = ?1 COLLATE NOCASE AND last_name = ?2 COLLATE NOCASE
+            ORDER BY dealer_id
+            ",
+        )?
+        .query_map(params![first_name, last_name], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(dealer_ids)
+}
+
+/// The dealer reachable on the contact's number, which must be valid.
+fn dealer_with_phone(connection: &Connection, contact: &Contact) -> Result<Option<i64>> {
+    let (country_code, phone_number) =
+        contact::normalize_phone(&contact.country_code, &contact.phone_number)?;
+    let dealer_id = connection.query_row(
+        "
+        SELECT MIN(dealer_id) FROM dealer_contact
+        JOIN phone ON phone.phone_id = dealer_contact.phone_id
+        WHERE phone.country_code = ?1 AND phone.phone_number = ?2
+        ",
+        params![country_code, phone_number],
+        |row| row.get(0),
+    )?;
+    Ok(dealer_id)
+}
+
+/// The category at `path`, like "Grocery > Oil". The first name may be
+/// any category's while no other category has it.
+fn category_id_for(connection: &Connection, path: &str) -> Result<i64> {
+    let mut names = path.split('>').map(str::trim);
+    let first = names.next().unwrap_or_default();
+
+    // Top-level categories first, as a bare name most likely means one.
+    let found: Vec<(i64, bool)> = connection
+        .prepare_cached(
+            "
+            SELECT category_id, parent_id IS NULL FROM category
+            WHERE name = ?1 COLLATE NOCASE
+            ORDER BY parent_id IS NOT NULL, category_id
+            ",
+        )?
+        .query_map(params![first], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut category_id = match found[..] {
+        [] => return Err(no_category(path)),
+        [(category_id, _)] | [(category_id, true), ..] => category_id,
+        _ => {
+            return Err(StoreError::validation(format!(
+                "Several categories are called {first}; write its path, like \"Grocery > {first}\""
+            )))
+        }
+    };
+
+    for name in names {
+        category_id = connection
+            .query_row(
+                "SELECT category_id FROM category WHERE parent_id = ?1 AND name = ?2 COLLATE NOCASE",
+                params![category_id, name],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| no_category(path))?;
+    }
+    Ok(category_id)
+}
+
+fn no_category(path: &str) -> StoreError {
+    StoreError::validation(format!("There is no category {path}"))
+}
+
+/// Reads "98765 43210 (office); +44 20 7946 0958" into contacts, the first
+/// one primary. Numbers are separated by semicolons and may carry a label in
+/// brackets.
+pub(crate) fn parse_phones(phones: &str) -> Result<Vec<Contact>> {
+    let mut contacts = Vec::new();
+    for entry in phones.split(';').map(str::trim) {
+        if entry.is_empty() {
+            continue;
+        }
+        let (phone_number, label) = match entry.strip_suffix(')').and_then(|e| e.split_once('(')) {
+            Some((phone_number, label)) => (phone_number.trim(), Some(label.trim().to_string())),
+            None => (entry, None),
+        };
+        let (country_code, phone_number) = contact::normalize_phone("", phone_number)?;
+        contacts.push(Contact {
+            country_code,
+            phone_number,
+            label,
+            is_primary: contacts.is_empty(),
+            ..Default::default()
+        });
+    }
+    Ok(contacts)
+}
+
+/// Lowercase, letters and digits only.
+fn header_key(header: &str) -> String {
+    header
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn reader(csv: &str) -> csv::Reader<&[u8]> {
+    ReaderBuilder::new()
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(csv.as_bytes())
+}
+
+fn invalid_csv(error: csv::Error) -> StoreError {
+    StoreError::validation(format!("The file could not be read as CSV: {error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn memory_store() -> Store {
+        Store::build(Path::new(":memory:")).unwrap()
+    }
+
+    fn mapping(kind: ImportKind, csv: &str) -> ColumnMapping {
+        ColumnMapping::from_headers(kind, &csv_headers(csv).unwrap())
+    }
+
+    fn outcomes(report: &ImportReport) -> Vec<(u64, &RowOutcome)> {
+        report
+            .rows
+            .iter()
+            .map(|row| (row.line, &row.outcome))
+            .collect()
+    }
+
+    #[test]
+    fn headers_are_matched_loosely_and_can_be_remapped() {
+        let csv = "Brand Name,PRODUCT,pack_size,Kind\nAmul,Butter,500g,Butter\n";
+        let mut mapping = mapping(ImportKind::Products, csv);
+        assert_eq!(mapping.column("brand"), Some(0));
+        assert_eq!(mapping.column("product"), Some(1));
+        assert_eq!(mapping.column("pack"), Some(2));
+        assert_eq!(mapping.missing(), vec!["item"]);
+
+        let mut store = memory_store();
+        assert!(matches!(
+            store.preview_import(csv, &mapping),
+            Err(StoreError::Validation(_))
+        ));
+
+        mapping.set("item", Some(3)).unwrap();
+        assert!(mapping.set("colour", Some(3)).is_err());
+        assert_eq!(store.import_csv(csv, &mapping).unwrap().new_rows(), 1);
+        assert_eq!(store.get_products().unwrap()[0].item_name, "Butter");
+    }
+
+    #[test]
+    fn preview_reports_rows_without_importing() {
+        let mut store = memory_store();
+        store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let csv = "\
+brand,product,item,pack
+AMUL, Butter ,butter,500G
+Amul,Butter,Butter,100g
+,Ghee,Ghee,1L
+
+Amul,Butter,Butter,100g
+";
+        let report = store
+            .preview_import(csv, &mapping(ImportKind::Products, csv))
+            .unwrap();
+        assert_eq!(
+            outcomes(&report),
+            vec![
+                (
+                    2,
+                    &RowOutcome::Duplicate("This product is already in the store".to_string())
+                ),
+                (3, &RowOutcome::New),
+                (
+                    4,
+                    &RowOutcome::Invalid("Brand name is required".to_string())
+                ),
+                (
+                    6,
+                    &RowOutcome::Duplicate("This product is already in the store".to_string())
+                ),
+            ]
+        );
+        assert_eq!(report.to_string(), "1 new, 2 duplicate, 1 invalid");
+        assert_eq!(store.get_products().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn an_invalid_row_imports_nothing() {
+        let mut store = memory_store();
+        let csv = "\
+first_name,last_name,phones
+Ramesh,Gupta,98765 43210 (shop); 99887 76655
+Suresh,Jain,12345
+";
+        let mapping = mapping(ImportKind::Dealers, csv);
+        assert!(matches!(
+            store.import_csv(csv, &mapping),
+            Err(StoreError::Validation(_))
+        ));
+        assert!(store.get_dealers().unwrap().is_empty());
+
+        let csv = csv.replace("12345", "");
+        let report = store.import_csv(&csv, &mapping).unwrap();
+        assert_eq!(report.new_rows(), 2);
+
+        let ramesh = store
+            .get_dealers()
+            .unwrap()
+            .into_iter()
+            .find(|dealer| dealer.first_name == "Ramesh")
+            .unwrap();
+        let primary = ramesh.primary_contact().unwrap();
+        assert_eq!(primary.e164(), "+919876543210");
+        assert_eq!(primary.label.as_deref(), Some("shop"));
+        assert_eq!(ramesh.contacts.len(), 2);
+
+        // Ramesh is recognised by his number, Suresh by his name.
+        let report = store
+            .preview_import(
+                "first_name,last_name,phone\nRamesh,G,+91 98765 43210\nsuresh,JAIN,\n",
+                &mapping,
+            )
+            .unwrap();
+        assert_eq!(report.duplicates(), 2);
+    }
+
+    #[test]
+    fn categories_are_found_by_path() {
+        let mut store = memory_store();
+        let csv = "\
+name,parent
+Grocery,
+Kitchen,
+Oil,Grocery
+Oil,Kitchen
+Mustard,Grocery > Oil
+";
+        let report = store
+            .import_csv(csv, &mapping(ImportKind::Categories, csv))
+            .unwrap();
+        assert_eq!(report.new_rows(), 5);
+
+        let csv = "\
+product,brand,item,pack,category
+Mustard Oil,Fortune,Oil,1L,grocery > oil > mustard
+Lamp Oil,Diya,Oil,1L,Kitchen > Oil
+Sunflower Oil,Fortune,Oil,1L,Oil
+Rice Bran Oil,Fortune,Oil,1L,Mustard
+Groundnut Oil,Fortune,Oil,1L,Grocery > Ghee
+";
+        let report = store
+            .preview_import(csv, &mapping(ImportKind::Products, csv))
+            .unwrap();
+        assert_eq!(
+            outcomes(&report),
+            vec![
+                (2, &RowOutcome::New),
+                (3, &RowOutcome::New),
+                (
+                    4,
+                    &RowOutcome::Invalid(
+                        "Several categories are called Oil; write its path, like \"Grocery > Oil\""
+                            .to_string()
+                    )
+                ),
+                (5, &RowOutcome::New),
+                (
+                    6,
+                    &RowOutcome::Invalid("There is no category Grocery > Ghee".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn prices_are_matched_to_products_and_dealers() {
+        let mut store = memory_store();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let ramesh = store
+            .add_dealer(
+                "Ramesh",
+                None,
+                "Gupta",
+                &parse_phones("9876543210").unwrap(),
+            )
+            .unwrap();
+        let csv = "\
+brand,product,pack,dealer_first_name,dealer_last_name,dealer_phone,price,date
+Amul,Butter,500g,,,98765 43210,₹250,2024-03-01 09:30:00
+amul,butter,500G,Ramesh,Gupta,,260,2024-04-01
+Amul,Butter,500g,Ramesh,Gupta,,250,2024-03-01 09:30:00
+Amul,Butter,1kg,Ramesh,Gupta,,500,
+Amul,Butter,500g,Suresh,Jain,,250,
+";
+        let error = store
+            .import_csv(csv, &mapping(ImportKind::Prices, csv))
+            .unwrap_err();
+        assert!(error.to_string().contains("line 5"), "{error}");
+
+        let csv = csv.lines().take(4).collect::<Vec<_>>().join("\n");
+        let report = store
+            .import_csv(&csv, &mapping(ImportKind::Prices, &csv))
+            .unwrap();
+        assert_eq!(report.to_string(), "2 new, 1 duplicate, 0 invalid");
+
+        let history: Vec<_> = store
+            .get_price_history(butter, ramesh)
+            .unwrap()
+            .into_iter()
+            .map(|point| (point.price, point.recorded_at))
+            .collect();
+        assert_eq!(
+            history,
+            vec![
+                (Money::from_rupees(250), "2024-03-01 09:30:00".to_string()),
+                (Money::from_rupees(260), "2024-04-01 00:00:00".to_string()),
+            ]
+        );
+    }
+}
@@ -1,25 +1,75 @@
-use levenshtein::levenshtein;
-use rusqlite::{params, Connection, Error, Result};
+use rusqlite::{params, Connection, OptionalExtension, Row};
 use std::fs;
 use std::path::Path;
 
+pub mod address;
+pub mod alias;
+pub mod archive;
+pub mod boost;
+pub mod category;
+pub mod comparison;
+pub mod contact;
+pub mod error;
+pub mod export;
+pub mod import;
+pub mod merge;
+pub mod migrations;
+pub mod money;
+pub mod pack;
+pub mod price;
+pub mod query;
+pub mod recent;
+pub mod search;
+pub mod supply;
+
+pub use address::Address;
+pub use alias::{Alias, AliasTarget, STARTER_ALIASES};
+pub use archive::DeletionImpact;
+pub use boost::SelectionWeighting;
+pub use category::Category;
+pub use comparison::ItemOffer;
+pub use contact::Contact;
+pub use error::{Result, StoreError};
+pub use import::{ColumnMapping, ImportField, ImportKind, ImportReport, ImportRow, RowOutcome};
+pub use money::Money;
+pub use pack::{Measure, Pack, Unit, UnitPrice};
+pub use price::{PriceChange, PricePoint, PriceSummary};
+pub use query::{ProductQuery, QueryField, QueryTerm};
+pub use recent::SearchContext;
+pub use search::{ProductFilter, ProductSort};
+
 // TODO: Use the database to the full capacity!
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Dealer {
+    pub dealer_id: i64,
     pub first_name: String,
     pub middle_name: Option<String>,
     pub last_name: String,
-    pub country_code: String,
-    pub phone_num: String,
+    pub contacts: Vec<Contact>,
+    pub archived: bool,
+}
+
+impl Dealer {
+    pub fn primary_contact(&self) -> Option<&Contact> {
+        self.contacts.iter().find(|contact| contact.is_primary)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Product {
+    pub product_id: i64,
     pub brand_name: String,
     pub product_name: String,
     pub item_name: String,
     pub pack_name: String,
+    /// The category the product's item is filed under.
+    pub category_id: Option<i64>,
+    pub brand_id: i64,
+    pub item_id: i64,
+    pub archived: bool,
+    /// The size `pack_name` describes, if it could be read.
+    pub pack: Option<Pack>,
 }
 
 pub struct Store {
@@ -27,329 +77,198 @@ pub struct Store {
 }
 
 impl Store {
-    pub fn build(path: &Path, new: bool) -> Result<Self> {
-        let connection = Connection::open(path)?;
-
-        if new {
-            connection.execute_batch(
-                "
-                PRAGMA foreign_keys = ON;
-
-                CREATE TABLE category (
-                    category_id INTEGER PRIMARY KEY,
-                    name TEXT NOT NULL CHECK (LENGTH(name) > 0),
-                    parent_id INT,
-                    FOREIGN KEY (parent_id) REFERENCES category(category_id) ON DELETE SET NULL
-                );
-
-                CREATE TABLE item (
-                    item_id INTEGER PRIMARY KEY,
-                    name TEXT NOT NULL CHECK (LENGTH(name) > 0),
-                    category_id INT DEFAULT NULL,
-                    FOREIGN KEY (category_id) REFERENCES category(category_id) ON DELETE SET NULL
-                );
-
-                CREATE TABLE brand (
-                    brand_id INTEGER PRIMARY KEY,
-                    name TEXT UNIQUE NOT NULL CHECK (LENGTH(name) > 0)
-                );
-
-                CREATE TABLE product (
-                    product_id INTEGER PRIMARY KEY,
-                    name TEXT NOT NULL CHECK (LENGTH(name) > 0),
-                    pack_name TEXT NOT NULL CHECK (LENGTH(pack_name) > 0),
-                    item_id INT NOT NULL,
-                    brand_id INT NOT NULL,
-                    FOREIGN KEY (item_id) REFERENCES item(item_id) ON DELETE CASCADE,
-                    FOREIGN KEY (brand_id) REFERENCES brand(brand_id) ON DELETE CASCADE
-                );
-
-                CREATE TABLE phone (
-                    phone_id INTEGER PRIMARY KEY,
-                    country_code TEXT NOT NULL CHECK (LENGTH(country_code) > 0),
-                    phone_number TEXT NOT NULL CHECK (LENGTH(phone_number) > 0)
-                );
-
-                CREATE TABLE address (
-                    address_id INTEGER PRIMARY KEY,
-                    house_num TEXT,  -- Optional
-                    street_name TEXT, -- Optional
-                    locality_name TEXT, -- Optional
-                    city_name TEXT NOT NULL CHECK (LENGTH(city_name) > 0),
-                    district_name TEXT NOT NULL CHECK (LENGTH(district_name) > 0),
-                    pin_code TEXT NOT NULL CHECK (LENGTH(pin_code) > 0),
-                    state TEXT NOT NULL CHECK (LENGTH(state) > 0),
-                    country TEXT NOT NULL CHECK (LENGTH(country) > 0)
-                );
-
-                CREATE TABLE dealer (
-                    dealer_id INTEGER PRIMARY KEY,
-                    first_name TEXT NOT NULL CHECK (LENGTH(first_name) > 0),
-                    middle_name TEXT,
-                    last_name TEXT NOT NULL CHECK (LENGTH(last_name) > 0)
-                );
-
-                CREATE TABLE dealer_location (
-                    dealer_id INT NOT NULL,
-                    address_id INT NOT NULL,
-                    description TEXT,
-                    PRIMARY KEY (dealer_id, address_id),
-                    FOREIGN KEY (dealer_id) REFERENCES dealer(dealer_id) ON DELETE CASCADE,
-                    FOREIGN KEY (address_id) REFERENCES address(address_id) ON DELETE CASCADE
-                );
-
-                CREATE TABLE dealer_contact (
-                    dealer_id INT NOT NULL,
-                    phone_id INT NOT NULL,
-                    description TEXT,
-                    PRIMARY KEY (dealer_id, phone_id),
-                    FOREIGN KEY (dealer_id) REFERENCES dealer(dealer_id) ON DELETE CASCADE,
-                    FOREIGN KEY (phone_id) REFERENCES phone(phone_id) ON DELETE CASCADE
-                );
-
-                CREATE TABLE dealer_price (
-                    product_id INT NOT NULL,
-                    dealer_id INT NOT NULL,
-                    price NUMERIC NOT NULL CHECK (price >= 0),
-                    time_stamp DATETIME DEFAULT CURRENT_TIMESTAMP,
-                    PRIMARY KEY (product_id, dealer_id, time_stamp),
-                    FOREIGN KEY (product_id) REFERENCES product(product_id) ON DELETE CASCADE,
-                    FOREIGN KEY (dealer_id) REFERENCES dealer(dealer_id) ON DELETE CASCADE
-                );
-                ",
-            )?;
-        }
+    /// Opens the database at `path`, creating it if needed, and migrates it to
+    /// the current schema version.
+    pub fn build(path: &Path) -> Result<Self> {
+        let mut connection = Connection::open(path)?;
+        migrations::migrate(&mut connection)?;
 
         Ok(Self { connection })
     }
 
-    pub fn get_dealers(&self) -> Result<Vec<Dealer>, Error> {
-        self.connection
-            .prepare(
-                "
-                    SELECT d.first_name, d.middle_name, d.last_name, p.country_code, p.phone_number
-                    FROM dealer d
-                    LEFT JOIN dealer_contact dc ON d.dealer_id = dc.dealer_id
-                    LEFT JOIN phone p ON dc.phone_id = p.phone_id
-                    ",
-            )?
-            .query_map((), |row| {
-                let first_name = row.get(0)?;
-                let middle_name = row.get(1)?;
-                let last_name = row.get(2)?;
-                let country_code = row.get(3)?;
-                let phone_num = row.get(4)?;
-                Ok(Dealer {
-                    first_name,
-                    middle_name,
-                    last_name,
-                    country_code,
-                    phone_num,
-                })
-            })?
-            .collect()
+    /// Every dealer that is not archived.
+    pub fn get_dealers(&self) -> Result<Vec<Dealer>> {
+        let mut dealers = self
+            .connection
+            .prepare(&format!("{DEALER_QUERY} WHERE archived_at IS NULL"))?
+            .query_map((), dealer_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for dealer in &mut dealers {
+            dealer.contacts = contact::load_contacts(&self.connection, dealer.dealer_id)?;
+        }
+        Ok(dealers)
+    }
+
+    pub fn get_dealer(&self, dealer_id: i64) -> Result<Dealer> {
+        let mut dealer = self
+            .connection
+            .query_row(
+                &format!("{DEALER_QUERY} WHERE dealer_id = ?1"),
+                params![dealer_id],
+                dealer_from_row,
+            )
+            .optional()?
+            .ok_or(StoreError::NotFound("dealer"))?;
+
+        dealer.contacts = contact::load_contacts(&self.connection, dealer_id)?;
+        Ok(dealer)
     }
 
+    /// Adds a dealer with the given numbers. The first contact marked primary,
+    /// or else the first one, becomes the primary contact.
     pub fn add_dealer(
         &mut self,
         first_name: &str,
         middle_name: Option<&str>,
         last_name: &str,
-        country_code: &str,
-        phone_number: &str,
-    ) -> Result<(), Error> {
+        contacts: &[Contact],
+    ) -> Result<i64> {
         let transaction = self.connection.transaction()?;
+        let dealer_id = insert_dealer(&transaction, first_name, middle_name, last_name, contacts)?;
+        transaction.commit()?;
+        Ok(dealer_id)
+    }
 
-        transaction.execute(
-            "INSERT INTO dealer (first_name, middle_name, last_name) VALUES(?1, ?2, ?3)",
-            params![first_name, middle_name, last_name],
-        )?;
-        let dealer_id = transaction.last_insert_rowid();
-
-        transaction.execute(
-            "INSERT OR IGNORE INTO phone (country_code, phone_number) VALUES(?1, ?2)",
-            params![country_code, phone_number],
-        )?;
-
-        let phone_id: i64 = transaction.query_row(
-            "SELECT phone_id FROM phone WHERE country_code = ?1 AND phone_number = ?2",
-            params![country_code, phone_number],
-            |row| row.get(0),
-        )?;
-
-        transaction.execute(
-            "INSERT INTO dealer_contact (dealer_id, phone_id) VALUES (?1, ?2)",
-            (dealer_id, phone_id),
+    /// Renames the dealer. Contacts, addresses and prices are untouched.
+    pub fn update_dealer(
+        &mut self,
+        dealer_id: i64,
+        first_name: &str,
+        middle_name: Option<&str>,
+        last_name: &str,
+    ) -> Result<()> {
+        let first_name = required("First name", first_name)?;
+        let middle_name = middle_name.map(str::trim).filter(|name| !name.is_empty());
+        let last_name = required("Last name", last_name)?;
+        self.ensure_exists("dealer", dealer_id)?;
+
+        self.connection.execute(
+            "UPDATE dealer SET first_name = ?2, middle_name = ?3, last_name = ?4 WHERE dealer_id = ?1",
+            params![dealer_id, first_name, middle_name, last_name],
         )?;
-
-        transaction.commit()?;
         Ok(())
     }
 
-    pub fn get_products(&self) -> Result<Vec<Product>, Error> {
+    /// Every product that is not archived.
+    pub fn get_products(&self) -> Result<Vec<Product>> {
+        let products = self
+            .connection
+            .prepare(&format!(
+                "{PRODUCT_QUERY} WHERE product.archived_at IS NULL"
+            ))?
+            .query_map((), product_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(products)
+    }
+
+    pub fn get_product(&self, product_id: i64) -> Result<Product> {
         self.connection
-            .prepare(
-                "
-                    SELECT product.name, product.pack_name, item.name, brand.name
-                    FROM product
-                    LEFT JOIN item ON item.item_id = product.item_id
-                    LEFT JOIN brand ON brand.brand_id = product.brand_id
-                    ",
-            )?
-            .query_map((), |row| {
-                let product_name = row.get(0)?;
-                let pack_name = row.get(1)?;
-                let item_name = row.get(2)?;
-                let brand_name = row.get(3)?;
-                Ok(Product {
-                    product_name,
-                    brand_name,
-                    item_name,
-                    pack_name,
-                })
-            })?
-            .collect()
+            .query_row(
+                &format!("{PRODUCT_QUERY} WHERE product.product_id = ?1"),
+                params![product_id],
+                product_from_row,
+            )
+            .optional()?
+            .ok_or(StoreError::NotFound("product"))
     }
 
+    /// Adds a product, creating its brand and item as needed. A `category_id`
+    /// files the item under that category, moving it if it was elsewhere.
     pub fn add_product(
         &mut self,
         product_name: &str,
         brand_name: &str,
         item_name: &str,
         pack_name: &str,
-    ) -> Result<(), Error> {
+        category_id: Option<i64>,
+    ) -> Result<i64> {
         let transaction = self.connection.transaction()?;
-
-        transaction.execute(
-            "INSERT OR IGNORE INTO item (name) VALUES(?1)",
-            params![item_name],
+        let product_id = insert_product(
+            &transaction,
+            product_name,
+            brand_name,
+            item_name,
+            pack_name,
+            category_id,
         )?;
+        transaction.commit()?;
+        Ok(product_id)
+    }
 
-        let item_id: i64 = transaction.query_row(
-            "SELECT item_id FROM item WHERE name = ?1",
-            params![item_name],
-            |row| row.get(0),
-        )?;
+    /// Rewrites the product's details. The brand and item are looked up by
+    /// name like in `add_product`, so fixing a misspelt brand re-points this
+    /// product instead of renaming the brand for every other product. Brands
+    /// and items nothing refers to any more are removed. Prices and dealer
+    /// links stay with the product.
+    pub fn update_product(
+        &mut self,
+        product_id: i64,
+        product_name: &str,
+        brand_name: &str,
+        item_name: &str,
+        pack_name: &str,
+        category_id: Option<i64>,
+    ) -> Result<()> {
+        let product_name = required("Product name", product_name)?;
+        let brand_name = required("Brand name", brand_name)?;
+        let item_name = required("Item name", item_name)?;
+        let pack_name = required("Pack name", pack_name)?;
+        self.ensure_exists("product", product_id)?;
+        if let Some(category_id) = category_id {
+            self.ensure_exists("category", category_id)?;
+        }
 
-        transaction.execute(
-            "INSERT OR IGNORE INTO brand (name) VALUES(?1)",
-            params![brand_name],
-        )?;
-        let brand_id: i64 = transaction.query_row(
-            "SELECT brand_id FROM brand WHERE name = ?1",
-            params![brand_name],
-            |row| row.get(0),
+        let transaction = self.connection.transaction()?;
+
+        let (old_item_id, old_brand_id): (i64, i64) = transaction.query_row(
+            "SELECT item_id, brand_id FROM product WHERE product_id = ?1",
+            params![product_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )?;
+        let item_id = item_id_for(&transaction, item_name, category_id)?;
+        let brand_id = brand_id_for(&transaction, brand_name)?;
 
+        let (pack_count, pack_quantity, pack_unit) = pack::pack_columns(pack_name);
         transaction.execute(
-            "INSERT INTO product (name, pack_name, brand_id, item_id) VALUES(?1, ?2, ?3, ?4)",
-            params![product_name, pack_name, brand_id, item_id],
+            "
+            UPDATE product
+            SET name = ?2, pack_name = ?3, brand_id = ?4, item_id = ?5,
+                pack_count = ?6, pack_quantity = ?7, pack_unit = ?8
+            WHERE product_id = ?1
+            ",
+            params![
+                product_id,
+                product_name,
+                pack_name,
+                brand_id,
+                item_id,
+                pack_count,
+                pack_quantity,
+                pack_unit
+            ],
         )?;
+        delete_if_unused(&transaction, old_item_id, old_brand_id)?;
 
         transaction.commit()?;
         Ok(())
     }
 
-    pub fn get_best_product_results_for(&self, new_text: &str) -> Result<Vec<Product>, Error> {
-        if new_text.is_empty() {
-            return Ok(Vec::new());
-        }
-
-        let products = self.get_products()?;
-        let query = new_text.to_lowercase();
-
-        let mut results: Vec<(Product, usize)> = products
-            .into_iter()
-            .map(|product| {
-                let mut score = 0;
-
-                let product_name = product.product_name.to_lowercase();
-                let brand_name = product.brand_name.to_lowercase();
-                let item_name = product.item_name.to_lowercase();
-                let pack_name = product.pack_name.to_lowercase();
-
-                if product_name == query
-                    || brand_name == query
-                    || item_name == query
-                    || pack_name == query
-                {
-                    score += 1000;
-                }
-
-                if product_name.starts_with(&query)
-                    || brand_name.starts_with(&query)
-                    || item_name.starts_with(&query)
-                    || pack_name.starts_with(&query)
-                {
-                    score += 500;
-                }
-
-                if product_name.contains(&query)
-                    || brand_name.contains(&query)
-                    || item_name.contains(&query)
-                    || pack_name.contains(&query)
-                {
-                    score += 200;
-                }
-
-                let distances = [
-                    levenshtein(&product_name, &query),
-                    levenshtein(&brand_name, &query),
-                    levenshtein(&item_name, &query),
-                    levenshtein(&pack_name, &query),
-                ];
-                let min_distance = *distances.iter().min().unwrap_or(&usize::MAX);
-
-                if min_distance <= 2 {
-                    score += 100 - min_distance * 30;
-                }
-
-                (product, score)
-            })
-            .filter(|(_, score)| *score > 0) // Remove low-relevance results
-            .collect();
-
-        results.sort_by(|a, b| b.1.cmp(&a.1));
-        results.truncate(5);
-
-        // Return only the products
-        Ok(results.into_iter().map(|(product, _)| product).collect())
-    }
-
-    pub fn get_recent_product_results(&self) -> Result<Vec<Product>, Error> {
-        // TODO: Implement a table in the database to account for recent searches!
-        Ok(Vec::new())
-    }
-
     pub fn get_latest_dealer_price_pairs_for(
         &self,
-        product: Product,
-    ) -> Result<Vec<(Dealer, u32)>, Error> {
-        let item_id: i64 = self.connection.query_row(
-            "SELECT item_id FROM item WHERE name = ?1",
-            params![product.item_name],
-            |row| row.get(0),
-        )?;
-        let brand_id: i64 = self.connection.query_row(
-            "SELECT brand_id FROM brand WHERE name = ?1",
-            params![product.brand_name],
-            |row| row.get(0),
-        )?;
-        let product_id: i64 = self.connection.query_row(
-            "SELECT product_id FROM product WHERE name = ?1 AND item_id = ?2 AND brand_id = ?3",
-            params![product.product_name, item_id, brand_id],
-            |row| row.get(0),
-        )?;
+        product_id: i64,
+    ) -> Result<Vec<(Dealer, Money)>> {
+        self.ensure_exists("product", product_id)?;
 
-        self.connection
+        let mut pairs = self
+            .connection
             .prepare(
                 "
-        SELECT d.first_name, d.middle_name, d.last_name, p.country_code, p.phone_number, price
+        SELECT d.dealer_id, d.first_name, d.middle_name, d.last_name, d.archived_at IS NOT NULL, price_paise
         FROM dealer_price dp
-        LEFT JOIN dealer d ON d.dealer_id = dp.dealer_id
-        LEFT JOIN dealer_contact dc ON d.dealer_id = dc.dealer_id
-        LEFT JOIN phone p ON dc.phone_id = p.phone_id
+        JOIN dealer d ON d.dealer_id = dp.dealer_id
         WHERE product_id = ?1
+        AND d.archived_at IS NULL
         AND dp.time_stamp = (
             SELECT MAX(dp2.time_stamp)
             FROM dealer_price dp2
@@ -359,92 +278,412 @@ impl Store {
         ",
             )?
             .query_map(params![product_id], |row| {
-                let first_name = row.get(0)?;
-                let middle_name = row.get(1)?;
-                let last_name = row.get(2)?;
-                let country_code = row.get(3)?;
-                let phone_num = row.get(4)?;
-                Ok((
-                    Dealer {
-                        first_name,
-                        middle_name,
-                        last_name,
-                        country_code,
-                        phone_num,
-                    },
-                    row.get(5)?,
-                ))
+                Ok((dealer_from_row(row)?, row.get(5)?))
             })?
-            .collect()
+            .collect::<rusqlite::Result<Vec<(Dealer, Money)>>>()?;
+
+        for (dealer, _) in &mut pairs {
+            dealer.contacts = contact::load_contacts(&self.connection, dealer.dealer_id)?;
+        }
+        Ok(pairs)
     }
 
-    pub fn update_price(&self, product: Product, dealer: Dealer, price: u32) {
-        println!(
-            "Product: {:#?}\nDealer: {:#?}\nPrice: {:#?}",
-            product, dealer, price
-        );
-        let product_id: i64 = self
+    /// Records the dealer's price for the product as of now.
+    pub fn update_price(&self, product_id: i64, dealer_id: i64, price: Money) -> Result<()> {
+        self.update_price_at(product_id, dealer_id, price, "now")
+    }
+
+    /// Reads a value from the `setting` table.
+    fn setting(&self, name: &str) -> Result<Option<String>> {
+        let value = self
             .connection
             .query_row(
-                "
-SELECT product_id
-FROM product
-LEFT JOIN brand ON brand.brand_id = product.brand_id
-LEFT JOIN item ON item.item_id = product.item_id
-WHERE product.name = ?1 AND brand.name = ?2 AND item.name = ?3 AND product.pack_name = ?4",
-                params![
-                    product.product_name,
-                    product.brand_name,
-                    product.item_name,
-                    product.pack_name
-                ],
+                "SELECT value FROM setting WHERE name = ?1",
+                params![name],
                 |row| row.get(0),
             )
-            .unwrap();
+            .optional()?;
+        Ok(value)
+    }
 
-        let dealer_id: i64 = self
-            .connection
-            .query_row(
-            "
-SELECT dealer.dealer_id
-FROM dealer
-LEFT JOIN dealer_contact ON dealer.dealer_id = dealer_contact.dealer_id
-LEFT JOIN phone ON dealer_contact.phone_id = phone.phone_id
-WHERE dealer.first_name = ?1 AND (dealer.middle_name IS NULL OR dealer.middle_name = ?2) AND dealer.last_name = ?3 AND phone.country_code = ?4 AND phone.phone_number = ?5",
-        params![dealer.first_name, dealer.middle_name, dealer.last_name, dealer.country_code, dealer.phone_num], |row| row.get(0)).unwrap();
+    fn set_setting(&self, name: &str, value: &str) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO setting (name, value) VALUES (?1, ?2)
+             ON CONFLICT (name) DO UPDATE SET value = excluded.value",
+            params![name, value],
+        )?;
+        Ok(())
+    }
 
-        self.connection
-            .execute(
-                "INSERT INTO dealer_price (product_id, dealer_id, price) VALUES (?1, ?2, ?3)",
-                params![product_id, dealer_id, price],
-            )
-            .unwrap();
+    /// Fails with `NotFound(table)` unless `table` has a row with `{table}_id = id`.
+    fn ensure_exists(&self, table: &'static str, id: i64) -> Result<()> {
+        ensure_exists(&self.connection, table, id)
     }
 }
 
-pub fn load_store_data_from(path: &Path) -> Result<Store> {
-    if fs::exists(path).expect("Unable to check if {path} exist!") {
-        return Store::build(path, false);
+/// Selects what `product_from_row` reads. Append WHERE, JOIN or ORDER BY
+/// clauses to narrow it down.
+const PRODUCT_QUERY: &str = "
+    SELECT product.product_id, product.name, product.pack_name, item.name, brand.name, item.category_id,
+        product.brand_id, product.item_id, product.archived_at IS NOT NULL,
+        product.pack_count, product.pack_quantity, product.pack_unit
+    FROM product
+    LEFT JOIN item ON item.item_id = product.item_id
+    LEFT JOIN brand ON brand.brand_id = product.brand_id
+    ";
+
+fn product_from_row(row: &Row) -> rusqlite::Result<Product> {
+    Ok(Product {
+        product_id: row.get(0)?,
+        product_name: row.get(1)?,
+        pack_name: row.get(2)?,
+        item_name: row.get(3)?,
+        brand_name: row.get(4)?,
+        category_id: row.get(5)?,
+        brand_id: row.get(6)?,
+        item_id: row.get(7)?,
+        archived: row.get(8)?,
+        pack: pack::pack_from_columns(row.get(9)?, row.get(10)?, row.get(11)?),
+    })
+}
+
+/// Selects what `dealer_from_row` reads.
+const DEALER_QUERY: &str =
+    "SELECT dealer_id, first_name, middle_name, last_name, archived_at IS NOT NULL FROM dealer";
+
+/// Reads `dealer_id, first_name, middle_name, last_name, archived` from the
+/// start of `row`. Contacts are loaded separately.
+fn dealer_from_row(row: &Row) -> rusqlite::Result<Dealer> {
+    Ok(Dealer {
+        dealer_id: row.get(0)?,
+        first_name: row.get(1)?,
+        middle_name: row.get(2)?,
+        last_name: row.get(3)?,
+        contacts: Vec::new(),
+        archived: row.get(4)?,
+    })
+}
+
+/// What `add_dealer` does, inside the caller's transaction.
+fn insert_dealer(
+    connection: &Connection,
+    first_name: &str,
+    middle_name: Option<&str>,
+    last_name: &str,
+    contacts: &[Contact],
+) -> Result<i64> {
+    let first_name = required("First name", first_name)?;
+    let middle_name = middle_name.map(str::trim).filter(|name| !name.is_empty());
+    let last_name = required("Last name", last_name)?;
+
+    connection.execute(
+        "INSERT INTO dealer (first_name, middle_name, last_name) VALUES(?1, ?2, ?3)",
+        params![first_name, middle_name, last_name],
+    )?;
+    let dealer_id = connection.last_insert_rowid();
+
+    let primary = contacts
+        .iter()
+        .position(|contact| contact.is_primary)
+        .unwrap_or(0);
+    for (index, contact) in contacts.iter().enumerate() {
+        contact::insert_contact(connection, dealer_id, contact, index == primary)?;
     }
+    Ok(dealer_id)
+}
 
-    if fs::exists(path.parent().expect("Failed to know the parent of a path"))
-        .expect("Unable to check parent of path!")
-    {
-        return Store::build(path, true);
+/// What `add_product` does, inside the caller's transaction.
+fn insert_product(
+    connection: &Connection,
+    product_name: &str,
+    brand_name: &str,
+    item_name: &str,
+    pack_name: &str,
+    category_id: Option<i64>,
+) -> Result<i64> {
+    let product_name = required("Product name", product_name)?;
+    let brand_name = required("Brand name", brand_name)?;
+    let item_name = required("Item name", item_name)?;
+    let pack_name = required("Pack name", pack_name)?;
+    if let Some(category_id) = category_id {
+        ensure_exists(connection, "category", category_id)?;
     }
 
-    if let Err(e) = fs::create_dir_all(path.parent().expect("Unable to know the parent of {path}!"))
-    {
-        let e = e.kind();
-        panic!(
-            "Unable to create file at {}! Error: {}!",
-            path.to_str().unwrap(),
-            e
-        );
+    let item_id = item_id_for(connection, item_name, category_id)?;
+    let brand_id = brand_id_for(connection, brand_name)?;
+
+    let (pack_count, pack_quantity, pack_unit) = pack::pack_columns(pack_name);
+    connection.execute(
+        "
+        INSERT INTO product (name, pack_name, brand_id, item_id, pack_count, pack_quantity, pack_unit)
+        VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ",
+        params![product_name, pack_name, brand_id, item_id, pack_count, pack_quantity, pack_unit],
+    )?;
+    Ok(connection.last_insert_rowid())
+}
+
+/// Fails with `NotFound(table)` unless `table` has a row with `{table}_id = id`.
+fn ensure_exists(connection: &Connection, table: &'static str, id: i64) -> Result<()> {
+    let exists: bool = connection.query_row(
+        &format!("SELECT EXISTS (SELECT 1 FROM {table} WHERE {table}_id = ?1)"),
+        params![id],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        return Err(StoreError::NotFound(table));
+    }
+    Ok(())
+}
+
+/// The item called `item_name`, ignoring case and spacing, created if there
+/// is none. A `category_id` files the item under that category, moving it if
+/// it was elsewhere.
+fn item_id_for(connection: &Connection, item_name: &str, category_id: Option<i64>) -> Result<i64> {
+    let item_name = tidy_name(item_name);
+    // Look the item up first rather than relying on INSERT OR IGNORE, so
+    // that an existing item can be moved to `category_id`.
+    let existing_item_id: Option<i64> = connection.query_row(
+        "SELECT MIN(item_id) FROM item WHERE name = ?1 COLLATE NOCASE",
+        params![item_name],
+        |row| row.get(0),
+    )?;
+    match existing_item_id {
+        Some(item_id) => {
+            if category_id.is_some() {
+                connection.execute(
+                    "UPDATE item SET category_id = ?2 WHERE item_id = ?1",
+                    params![item_id, category_id],
+                )?;
+            }
+            Ok(item_id)
+        }
+        None => {
+            connection.execute(
+                "INSERT INTO item (name, category_id) VALUES (?1, ?2)",
+                params![item_name, category_id],
+            )?;
+            Ok(connection.last_insert_rowid())
+        }
+    }
+}
+
+/// The brand called `brand_name`, ignoring case and spacing, created if
+/// there is none.
+fn brand_id_for(connection: &Connection, brand_name: &str) -> Result<i64> {
+    let brand_name = tidy_name(brand_name);
+    connection.execute(
+        "INSERT OR IGNORE INTO brand (name) VALUES(?1)",
+        params![brand_name],
+    )?;
+    let brand_id = connection.query_row(
+        "SELECT brand_id FROM brand WHERE name = ?1 COLLATE NOCASE",
+        params![brand_name],
+        |row| row.get(0),
+    )?;
+    Ok(brand_id)
+}
+
+/// Deletes the item and the brand if no product is left using them.
+fn delete_if_unused(connection: &Connection, item_id: i64, brand_id: i64) -> Result<()> {
+    connection.execute(
+        "DELETE FROM item WHERE item_id = ?1 AND NOT EXISTS (SELECT 1 FROM product WHERE item_id = ?1)",
+        params![item_id],
+    )?;
+    connection.execute(
+        "DELETE FROM brand WHERE brand_id = ?1 AND NOT EXISTS (SELECT 1 FROM product WHERE brand_id = ?1)",
+        params![brand_id],
+    )?;
+    Ok(())
+}
+
+/// Trims `name` and collapses the spaces inside it, as brand and item names
+/// are stored.
+fn tidy_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Trims `value` and rejects it if nothing is left.
+fn required<'a>(field: &str, value: &'a str) -> Result<&'a str> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(StoreError::validation(format!("{field} is required")));
+    }
+    Ok(value)
+}
+
+pub fn load_store_data_from(path: &Path) -> Result<Store> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
     }
 
-    Store::build(path, true)
+    Store::build(path)
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_db_path(name: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!("gsm-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        path.push("store.db");
+        path
+    }
+
+    #[test]
+    fn load_creates_database_and_reopens_it() {
+        let path = temp_db_path("reopen");
+
+        {
+            let mut store = load_store_data_from(&path).unwrap();
+            store
+                .add_product("Butter", "Amul", "Butter", "500g", None)
+                .unwrap();
+        }
+
+        let store = load_store_data_from(&path).unwrap();
+        assert_eq!(store.get_products().unwrap().len(), 1);
+        assert_eq!(
+            migrations::schema_version(&store.connection).unwrap(),
+            migrations::CURRENT_VERSION
+        );
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    fn memory_store() -> Store {
+        Store::build(Path::new(":memory:")).unwrap()
+    }
+
+    #[test]
+    fn blank_fields_are_rejected() {
+        let mut store = memory_store();
+
+        let error = store
+            .add_product("Butter", "  ", "Butter", "500g", None)
+            .unwrap_err();
+        assert!(matches!(error, StoreError::Validation(_)));
+
+        let error = store.add_dealer("", None, "Gupta", &[]).unwrap_err();
+        assert!(matches!(error, StoreError::Validation(_)));
+    }
+
+    #[test]
+    fn price_for_unknown_dealer_is_not_found() {
+        let mut store = memory_store();
+        let product_id = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+
+        let error = store
+            .update_price(product_id, 42, Money::from_rupees(42))
+            .unwrap_err();
+        assert!(matches!(error, StoreError::NotFound("dealer")));
+    }
+
+    #[test]
+    fn rows_are_told_apart_by_id() {
+        let mut store = memory_store();
+        let small = store
+            .add_product("Butter", "Amul", "Butter", "100g", None)
+            .unwrap();
+        let large = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let first = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+        let second = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+
+        store
+            .update_price(small, first, Money::from_rupees(55))
+            .unwrap();
+        store
+            .update_price(large, second, Money::from_paise(24950))
+            .unwrap();
+
+        let pairs = store.get_latest_dealer_price_pairs_for(large).unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].0.dealer_id, second);
+        assert_eq!(pairs[0].1, Money::from_paise(24950));
+
+        let ids: Vec<_> = store
+            .get_products()
+            .unwrap()
+            .iter()
+            .map(|product| product.product_id)
+            .collect();
+        assert_eq!(ids, vec![small, large]);
+    }
+
+    #[test]
+    fn edits_keep_prices_and_tidy_up_brands() {
+        let mut store = memory_store();
+        let typo = store
+            .add_product("Butter", "Amull", "Butter", "500g", None)
+            .unwrap();
+        let other = store
+            .add_product("Cheese", "Amul", "Cheese", "200g", None)
+            .unwrap();
+        let dealer_id = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+        store
+            .update_price(typo, dealer_id, Money::from_rupees(250))
+            .unwrap();
+
+        store
+            .update_product(typo, "Salted Butter", "Amul", "Butter", "500 g", None)
+            .unwrap();
+        let product = store.get_product(typo).unwrap();
+        assert_eq!(
+            (product.product_name.as_str(), product.brand_name.as_str()),
+            ("Salted Butter", "Amul")
+        );
+        assert_eq!(store.get_price_history(typo, dealer_id).unwrap().len(), 1);
+        assert_eq!(store.get_product(other).unwrap().brand_name, "Amul");
+        let brands: i64 = store
+            .connection
+            .query_row("SELECT COUNT(*) FROM brand", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(brands, 1);
+
+        store
+            .update_dealer(dealer_id, "Ramesh", Some("Kumar"), "Gupta")
+            .unwrap();
+        let dealer = store.get_dealer(dealer_id).unwrap();
+        assert_eq!(dealer.middle_name.as_deref(), Some("Kumar"));
+
+        assert!(matches!(
+            store.update_product(42, "Butter", "Amul", "Butter", "500g", None),
+            Err(StoreError::NotFound("product"))
+        ));
+        assert!(matches!(
+            store.update_dealer(dealer_id, " ", None, "Gupta"),
+            Err(StoreError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn brands_and_items_ignore_case_and_spacing() {
+        let mut store = memory_store();
+        let butter = store
+            .add_product("Butter", "Mother Dairy", "Butter", "500g", None)
+            .unwrap();
+        let ghee = store
+            .add_product("Ghee", " mother  dairy ", "BUTTER", "1L", None)
+            .unwrap();
+
+        let butter = store.get_product(butter).unwrap();
+        let ghee = store.get_product(ghee).unwrap();
+        assert_eq!(ghee.brand_id, butter.brand_id);
+        assert_eq!(ghee.item_id, butter.item_id);
+        assert_eq!(ghee.brand_name, "Mother Dairy");
+
+        store
+            .update_product(ghee.product_id, "Ghee", "Mother Dairy ", "Ghee", "1L", None)
+            .unwrap();
+        assert_eq!(store.get_brands().unwrap().len(), 1);
+        assert_eq!(store.get_items().unwrap().len(), 2);
+    }
+}
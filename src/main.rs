@@ -1,10 +1,481 @@
 slint::include_modules!();
-use general_store_manager::{load_store_data_from, Dealer, Product};
-use slint::{Model, ModelRc, ToSharedString, VecModel};
+use general_store_manager::{
+    load_store_data_from, Address, Alias, AliasTarget, Category, Contact, Dealer, DeletionImpact,
+    ItemOffer, Money, Product, ProductFilter, ProductSort, SearchContext, SelectionWeighting,
+    Store, StoreError,
+};
+use slint::{Model, ModelRc, SharedString, ToSharedString, VecModel};
 use std::cell::RefCell;
 use std::env;
+use std::process;
 use std::rc::Rc;
 
+/// Shows a failed store call in the error dialog and hands back the value on success.
+fn report<T>(app: &MainWindow, result: Result<T, StoreError>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            app.set_error_message(e.to_shared_string());
+            None
+        }
+    }
+}
+
+/// Like `report`, but a mistake in the search text comes back as the message
+/// to show under the search box, with no results.
+fn report_search(
+    app: &MainWindow,
+    result: Result<Vec<Product>, StoreError>,
+) -> Option<(Vec<Product>, String)> {
+    match result {
+        Err(StoreError::InvalidQuery(message)) => Some((Vec::new(), message)),
+        result => report(app, result).map(|products| (products, String::new())),
+    }
+}
+
+/// Narrows a row id to Slint's 32-bit int, refusing one that would come back
+/// naming a different record.
+fn slint_id(id: i64) -> Result<i32, StoreError> {
+    i32::try_from(id).map_err(|_| {
+        StoreError::Validation(format!("Record #{id} is too large for the window to show"))
+    })
+}
+
+/// Narrows a count for display, showing the largest int for one past it.
+fn slint_count(count: impl TryInto<i32>) -> i32 {
+    count.try_into().unwrap_or(i32::MAX)
+}
+
+/// Converts every record for the window, stopping at the first that cannot be.
+fn data_model<T, D: Clone + 'static>(
+    records: impl IntoIterator<Item = T>,
+    to_data: impl FnMut(T) -> Result<D, StoreError>,
+) -> Result<ModelRc<D>, StoreError> {
+    let data = records
+        .into_iter()
+        .map(to_data)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ModelRc::new(VecModel::from(data)))
+}
+
+fn product_data(product: Product) -> Result<slint_generatedMainWindow::ProductData, StoreError> {
+    Ok(slint_generatedMainWindow::ProductData {
+        product_id: slint_id(product.product_id)?,
+        brand_name: product.brand_name.to_shared_string(),
+        pack_name: product.pack_name.to_shared_string(),
+        product_name: product.product_name.to_shared_string(),
+        item_name: product.item_name.to_shared_string(),
+        category_id: slint_id(product.category_id.unwrap_or_default())?,
+        brand_id: slint_id(product.brand_id)?,
+        item_id: slint_id(product.item_id)?,
+        archived: product.archived,
+        ..Default::default()
+    })
+}
+
+fn item_offer_data(
+    offer: ItemOffer,
+) -> Result<slint_generatedMainWindow::ItemOfferData, StoreError> {
+    Ok(slint_generatedMainWindow::ItemOfferData {
+        product: product_data(offer.product)?,
+        dealer: dealer_data(offer.dealer)?,
+        price: offer.price.to_shared_string(),
+        unit_price: offer
+            .unit_price
+            .map(|unit_price| unit_price.to_shared_string())
+            .unwrap_or_default(),
+    })
+}
+
+fn category_data(
+    category: Category,
+) -> Result<slint_generatedMainWindow::CategoryData, StoreError> {
+    Ok(slint_generatedMainWindow::CategoryData {
+        category_id: slint_id(category.category_id)?,
+        name: category.name.to_shared_string(),
+        depth: slint_count(category.depth),
+    })
+}
+
+fn contact_data(contact: &Contact) -> Result<slint_generatedMainWindow::ContactData, StoreError> {
+    Ok(slint_generatedMainWindow::ContactData {
+        phone_id: slint_id(contact.phone_id)?,
+        country_code: contact.country_code.to_shared_string(),
+        phone_num: contact.phone_number.to_shared_string(),
+        formatted: contact.formatted().to_shared_string(),
+        label: contact.label.clone().unwrap_or_default().to_shared_string(),
+        is_primary: contact.is_primary,
+    })
+}
+
+fn contact_from(data: &slint_generatedMainWindow::ContactData) -> Contact {
+    Contact {
+        phone_id: data.phone_id.into(),
+        country_code: data.country_code.to_string(),
+        phone_number: data.phone_num.to_string(),
+        label: Some(data.label.to_string()).filter(|label| !label.is_empty()),
+        is_primary: data.is_primary,
+    }
+}
+
+fn dealer_data(dealer: Dealer) -> Result<slint_generatedMainWindow::DealerData, StoreError> {
+    let primary = dealer
+        .primary_contact()
+        .map(contact_data)
+        .transpose()?
+        .unwrap_or_default();
+    let contacts = data_model(&dealer.contacts, contact_data)?;
+
+    Ok(slint_generatedMainWindow::DealerData {
+        dealer_id: slint_id(dealer.dealer_id)?,
+        first_name: dealer.first_name.to_shared_string(),
+        middle_name: dealer.middle_name.unwrap_or_default().to_shared_string(),
+        last_name: dealer.last_name.to_shared_string(),
+        country_code: primary.country_code,
+        phone_num: primary.phone_num,
+        formatted_phone: primary.formatted,
+        contacts,
+        archived: dealer.archived,
+        ..Default::default()
+    })
+}
+
+fn impact_data(impact: DeletionImpact) -> slint_generatedMainWindow::DeletionImpactData {
+    slint_generatedMainWindow::DeletionImpactData {
+        products: slint_count(impact.products),
+        prices: slint_count(impact.prices),
+        links: slint_count(impact.links),
+    }
+}
+
+fn alias_data(alias: Alias) -> Result<slint_generatedMainWindow::AliasData, StoreError> {
+    Ok(slint_generatedMainWindow::AliasData {
+        alias_id: slint_id(alias.alias_id)?,
+        name: alias.name.to_shared_string(),
+        target: match alias.target {
+            AliasTarget::Product(_) => AliasFor::Product,
+            AliasTarget::Brand(_) => AliasFor::Brand,
+            AliasTarget::Item(_) => AliasFor::Item,
+        },
+    })
+}
+
+fn address_data(address: Address) -> Result<slint_generatedMainWindow::AddressData, StoreError> {
+    Ok(slint_generatedMainWindow::AddressData {
+        address_id: slint_id(address.address_id)?,
+        description: address.description.unwrap_or_default().to_shared_string(),
+        house_num: address.house_num.unwrap_or_default().to_shared_string(),
+        street_name: address.street_name.unwrap_or_default().to_shared_string(),
+        locality_name: address.locality_name.unwrap_or_default().to_shared_string(),
+        city_name: address.city_name.to_shared_string(),
+        district_name: address.district_name.to_shared_string(),
+        pin_code: address.pin_code.to_shared_string(),
+        state: address.state.to_shared_string(),
+        country: address.country.to_shared_string(),
+    })
+}
+
+fn address_from(data: &slint_generatedMainWindow::AddressData) -> Address {
+    let optional = |text: &SharedString| Some(text.to_string()).filter(|text| !text.is_empty());
+    Address {
+        address_id: data.address_id.into(),
+        description: optional(&data.description),
+        house_num: optional(&data.house_num),
+        street_name: optional(&data.street_name),
+        locality_name: optional(&data.locality_name),
+        city_name: data.city_name.to_string(),
+        district_name: data.district_name.to_string(),
+        pin_code: data.pin_code.to_string(),
+        state: data.state.to_string(),
+        country: data.country.to_string(),
+    }
+}
+
+/// Loads everything the dealer detail dialog shows for the dealer.
+fn show_dealer_detail(app: &MainWindow, store: &Store, dealer_id: i64) {
+    let Some(dealer) = report(app, store.get_dealer(dealer_id)) else {
+        return;
+    };
+    let Some(addresses) = report(app, store.get_dealer_addresses(dealer_id)) else {
+        return;
+    };
+    let Some(products) = report(app, store.get_dealer_products(dealer_id)) else {
+        return;
+    };
+    let Some(all_products) = report(app, store.get_products()) else {
+        return;
+    };
+    let Some(impact) = report(app, store.preview_dealer_deletion(dealer_id)) else {
+        return;
+    };
+    let Some(duplicates) = report(app, store.get_dealer_duplicates(dealer_id)) else {
+        return;
+    };
+    let other_products: Vec<_> = all_products
+        .into_iter()
+        .filter(|product| {
+            !products
+                .iter()
+                .any(|linked| linked.product_id == product.product_id)
+        })
+        .collect();
+    let detail = dealer_data(dealer).and_then(|dealer| {
+        Ok(slint_generatedMainWindow::DealerDetailInput {
+            dealer,
+            addresses: data_model(addresses, address_data)?,
+            other_products: data_model(other_products, product_data)?,
+            products: data_model(products, product_data)?,
+            impact: impact_data(impact),
+            duplicates: data_model(duplicates, dealer_data)?,
+        })
+    });
+    let Some(detail) = report(app, detail) else {
+        return;
+    };
+
+    // The dealer list stays visible behind the dialog, so keep the rest of the data.
+    let mut input_data = app.get_input_data();
+    input_data.dealer_detail_input = detail;
+    app.set_input_data(input_data);
+}
+
+/// Loads the product detail dialog: the product and who stocks it.
+fn show_product_detail(app: &MainWindow, store: &Store, product_id: i64) {
+    let Some(product) = report(app, store.get_product(product_id)) else {
+        return;
+    };
+    let Some(dealers) = report(app, store.get_product_dealers(product_id)) else {
+        return;
+    };
+    let Some(all_dealers) = report(app, store.get_dealers()) else {
+        return;
+    };
+    let impacts = store
+        .preview_product_deletion(product_id)
+        .and_then(|product_impact| {
+            Ok((
+                product_impact,
+                store.preview_brand_deletion(product.brand_id)?,
+                store.preview_item_deletion(product.item_id)?,
+            ))
+        });
+    let Some((product_impact, brand_impact, item_impact)) = report(app, impacts) else {
+        return;
+    };
+    let Some(aliases) = report(app, store.get_product_aliases(product_id)) else {
+        return;
+    };
+    let Some(duplicates) = report(app, store.get_product_duplicates(product_id)) else {
+        return;
+    };
+    let other_dealers: Vec<_> = all_dealers
+        .into_iter()
+        .filter(|dealer| {
+            !dealers
+                .iter()
+                .any(|linked| linked.dealer_id == dealer.dealer_id)
+        })
+        .collect();
+    let detail = product_data(product).and_then(|product| {
+        Ok(slint_generatedMainWindow::ProductDetailInput {
+            product,
+            other_dealers: data_model(other_dealers, dealer_data)?,
+            dealers: data_model(dealers, dealer_data)?,
+            product_impact: impact_data(product_impact),
+            brand_impact: impact_data(brand_impact),
+            item_impact: impact_data(item_impact),
+            aliases: data_model(aliases, alias_data)?,
+            duplicates: data_model(duplicates, product_data)?,
+        })
+    });
+    let Some(detail) = report(app, detail) else {
+        return;
+    };
+
+    // The product list stays visible behind the dialog, so keep the rest of the data.
+    let mut input_data = app.get_input_data();
+    input_data.product_detail_input = detail;
+    app.set_input_data(input_data);
+}
+
+fn facet_data(
+    (id, name): (i64, String),
+) -> Result<slint_generatedMainWindow::FacetData, StoreError> {
+    Ok(slint_generatedMainWindow::FacetData {
+        id: slint_id(id)?,
+        name: name.to_shared_string(),
+    })
+}
+
+fn selection_weighting_data(
+    weighting: SelectionWeighting,
+) -> slint_generatedMainWindow::SelectionWeightingData {
+    slint_generatedMainWindow::SelectionWeightingData {
+        max_points: slint_count(weighting.max_points),
+        half_life_days: slint_count(weighting.half_life_days),
+    }
+}
+
+/// Reads the settings boxes, which only take whole numbers.
+fn selection_weighting_from(
+    max_points: &str,
+    half_life_days: &str,
+) -> Result<SelectionWeighting, StoreError> {
+    let number = |text: &str| {
+        text.trim().parse().map_err(|_| {
+            StoreError::Validation(format!("\"{}\" is not a whole number", text.trim()))
+        })
+    };
+    Ok(SelectionWeighting {
+        max_points: number(max_points)?,
+        half_life_days: number(half_life_days)?,
+    })
+}
+
+fn product_filter_from(data: &slint_generatedMainWindow::ProductFilterData) -> ProductFilter {
+    let id = |id: i32| Some(id.into()).filter(|id: &i64| *id > 0);
+    ProductFilter {
+        text: data.text.to_string(),
+        brand_id: id(data.brand_id),
+        item_id: id(data.item_id),
+        category_id: id(data.category_id),
+        archived: data.archived,
+        unparsed_packs: data.unparsed_packs,
+        sort: match data.sort {
+            SortOrder::Relevance => ProductSort::Relevance,
+            SortOrder::Name => ProductSort::Name,
+            SortOrder::Brand => ProductSort::Brand,
+            SortOrder::RecentlyPriced => ProductSort::RecentlyPriced,
+        },
+    }
+}
+
+/// Lists the products passing `filter` next to the category, brand and item
+/// lists it is picked from.
+fn show_products(
+    app: &MainWindow,
+    store: &Store,
+    filter: slint_generatedMainWindow::ProductFilterData,
+) {
+    let Some(categories) = report(app, store.get_categories()) else {
+        return;
+    };
+    let Some(brands) = report(app, store.get_brands()) else {
+        return;
+    };
+    let Some(items) = report(app, store.get_items()) else {
+        return;
+    };
+    let Some((products, error)) =
+        report_search(app, store.search_products(&product_filter_from(&filter)))
+    else {
+        return;
+    };
+    let input = (|| {
+        Ok(slint_generatedMainWindow::ProductSearchInput {
+            results: data_model(products, product_data)?,
+            categories: data_model(categories, category_data)?,
+            brands: data_model(brands, facet_data)?,
+            items: data_model(items, facet_data)?,
+            filter,
+            error: error.to_shared_string(),
+        })
+    })();
+    let Some(input) = report(app, input) else {
+        return;
+    };
+
+    app.set_input_data(slint_generatedMainWindow::Data {
+        product_search_input: input,
+        ..Default::default()
+    });
+}
+
+/// Lists the active dealers, or the archived ones.
+fn show_dealers(app: &MainWindow, store: &Store, archived: bool) {
+    let dealers = if archived {
+        store.get_archived_dealers()
+    } else {
+        store.get_dealers()
+    };
+    let Some(dealers) = report(app, dealers) else {
+        return;
+    };
+    let Some(dealers) = report(app, data_model(dealers, dealer_data)) else {
+        return;
+    };
+
+    app.set_input_data(slint_generatedMainWindow::Data {
+        dealer_search_input: slint_generatedMainWindow::DealerSearchInput {
+            results: dealers,
+            show_archived: archived,
+        },
+        ..Default::default()
+    });
+}
+
+/// Lists the active dealers best matching `text`, or all of them while the
+/// search box is empty.
+fn show_dealer_results(app: &MainWindow, store: &Store, text: &str) {
+    if text.trim().is_empty() {
+        show_dealers(app, store, false);
+        return;
+    }
+    let Some(dealers) = report(app, store.get_best_dealer_results_for(text)) else {
+        return;
+    };
+    let Some(dealers) = report(app, data_model(dealers, dealer_data)) else {
+        return;
+    };
+
+    app.set_input_data(slint_generatedMainWindow::Data {
+        dealer_search_input: slint_generatedMainWindow::DealerSearchInput {
+            results: dealers,
+            show_archived: false,
+        },
+        ..Default::default()
+    });
+}
+
+/// The product search box `mode` belongs to, if it has one.
+fn search_context(mode: Mode) -> Option<SearchContext> {
+    match mode {
+        Mode::ComparisonProductSelection | Mode::ComparisonTable => Some(SearchContext::Comparison),
+        Mode::DataFeedSelection | Mode::DataFeedProcedure => Some(SearchContext::DataFeed),
+        _ => None,
+    }
+}
+
+/// Fills the product search dropdown with matches for `text` and the
+/// products recently picked from the same box.
+fn show_product_selection(app: &MainWindow, store: &Store, text: &str, context: SearchContext) {
+    let Some((best_results, error)) = report_search(app, store.get_best_product_results_for(text))
+    else {
+        return;
+    };
+    let Some(recent_results) = report(app, store.get_recent_product_results(context)) else {
+        return;
+    };
+
+    let Some(best_products) = report(app, data_model(best_results, product_data)) else {
+        return;
+    };
+    let Some(recent_products) = report(app, data_model(recent_results, product_data)) else {
+        return;
+    };
+
+    let input = slint_generatedMainWindow::ProductSelectionInput {
+        best_results: best_products,
+        recent_results: recent_products,
+        error: error.to_shared_string(),
+    };
+
+    app.set_input_data(slint_generatedMainWindow::Data {
+        product_selection_input: input,
+        ..Default::default()
+    });
+}
+
 fn main() {
     let app = MainWindow::new().unwrap();
 
@@ -12,52 +483,56 @@ fn main() {
     cwd.push("store.db");
     let path = cwd.as_path();
 
-    let store = Rc::new(RefCell::new(
-        load_store_data_from(path).expect("Failed to make/load store!"),
-    ));
+    let store = match load_store_data_from(path) {
+        Ok(store) => Rc::new(RefCell::new(store)),
+        Err(e) => {
+            eprintln!("Unable to open {}: {e}", path.display());
+            process::exit(1);
+        }
+    };
+
+    // Numbers the normalization pass could not parse need fixing by hand.
+    if let Some(unparsed) = report(&app, store.borrow().get_unparsed_contacts()) {
+        if !unparsed.is_empty() {
+            let lines: Vec<_> = unparsed
+                .iter()
+                .map(|(dealer_id, contact)| {
+                    format!(
+                        "Dealer #{dealer_id}: {} {}",
+                        contact.country_code, contact.phone_number
+                    )
+                })
+                .collect();
+            app.set_error_message(
+                format!(
+                    "These phone numbers could not be understood and were left as typed:\n{}",
+                    lines.join("\n")
+                )
+                .into(),
+            );
+        }
+    }
 
     // Search Text Change Callback
     {
         let weak_store = Rc::downgrade(&store);
         let weak_app = app.as_weak();
         app.on_search_text_changed(move |new_text, mode| {
-            if mode == Mode::ComparisonProductSelection || mode == Mode::DataFeedSelection {
-                let store = weak_store.upgrade().unwrap();
-                let store = store.borrow();
-                let best_results = store.get_best_product_results_for(&new_text).unwrap();
-                let recent_results = store.get_recent_product_results().unwrap();
-
-                let best_products: Vec<_> = best_results
-                    .into_iter()
-                    .map(|product| slint_generatedMainWindow::ProductData {
-                        brand_name: product.brand_name.to_shared_string(),
-                        pack_name: product.pack_name.to_shared_string(),
-                        product_name: product.product_name.to_shared_string(),
-                        item_name: product.item_name.to_shared_string(),
-                        ..Default::default()
-                    })
-                    .collect();
-                let recent_products: Vec<_> = recent_results
-                    .into_iter()
-                    .map(|product| slint_generatedMainWindow::ProductData {
-                        brand_name: product.brand_name.into(),
-                        pack_name: product.pack_name.to_shared_string(),
-                        product_name: product.product_name.to_shared_string(),
-                        item_name: product.item_name.to_shared_string(),
-                        ..Default::default()
-                    })
-                    .collect();
-
-                let input = slint_generatedMainWindow::ProductSelectionInput {
-                    best_results: ModelRc::new(VecModel::from(best_products)),
-                    recent_results: ModelRc::new(VecModel::from(recent_products)),
-                };
-
-                let app = weak_app.upgrade().unwrap();
-                app.set_input_data(slint_generatedMainWindow::Data {
-                    product_selection_input: input,
-                    ..Default::default()
-                });
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let store = store.borrow();
+            match mode {
+                Mode::DealerSearch => show_dealer_results(&app, &store, &new_text),
+                Mode::ProductSearch => {
+                    let mut filter = app.get_input_data().product_search_input.filter;
+                    filter.text = new_text;
+                    show_products(&app, &store, filter);
+                }
+                _ => {
+                    if let Some(context) = search_context(mode) {
+                        show_product_selection(&app, &store, &new_text, context);
+                    }
+                }
             }
         });
     }
@@ -67,96 +542,534 @@ fn main() {
         let weak_store = Rc::downgrade(&store);
         let weak_app = app.as_weak();
         app.on_mode_changed(move |mode| match mode {
+            Mode::ComparisonProductSelection | Mode::DataFeedSelection => {
+                let app = weak_app.upgrade().unwrap();
+                let store = weak_store.upgrade().unwrap();
+                let store = store.borrow();
+                if let Some(context) = search_context(mode) {
+                    show_product_selection(&app, &store, "", context);
+                }
+            }
             Mode::DealerSearch => {
+                let app = weak_app.upgrade().unwrap();
                 let store = weak_store.upgrade().unwrap();
                 let store = store.borrow();
-                let dealers = store.get_dealers().unwrap();
-                let modified_dealers: Vec<_> = dealers
-                    .into_iter()
-                    .map(|dealer| slint_generatedMainWindow::DealerData {
-                        first_name: dealer.first_name.to_shared_string(),
-                        middle_name: dealer.middle_name.unwrap_or_default().to_shared_string(),
-                        last_name: dealer.last_name.to_shared_string(),
-                        country_code: dealer.country_code.to_shared_string(),
-                        phone_num: dealer.phone_num.to_shared_string(),
-                        ..Default::default()
-                    })
-                    .collect();
 
-                let input = slint_generatedMainWindow::DealerSearchInput {
-                    results: ModelRc::new(VecModel::from(modified_dealers)),
-                };
-                let app = weak_app.upgrade().unwrap();
-                app.set_input_data(slint_generatedMainWindow::Data {
-                    dealer_search_input: input,
-                    ..Default::default()
-                });
+                // Closing a dealer should return to the list it was opened from.
+                let archived = app.get_input_data().dealer_search_input.show_archived;
+                show_dealers(&app, &store, archived);
             }
             Mode::ProductSearch => {
+                let app = weak_app.upgrade().unwrap();
                 let store = weak_store.upgrade().unwrap();
                 let store = store.borrow();
 
-                let products = store.get_products().unwrap();
-                let modified_products: Vec<_> = products
-                    .into_iter()
-                    .map(|product| slint_generatedMainWindow::ProductData {
-                        brand_name: product.brand_name.to_shared_string(),
-                        pack_name: product.pack_name.to_shared_string(),
-                        product_name: product.product_name.to_shared_string(),
-                        item_name: product.item_name.to_shared_string(),
-                        ..Default::default()
-                    })
-                    .collect();
+                // Coming back from a dialog should not lose the search or filters.
+                let filter = app.get_input_data().product_search_input.filter;
+                show_products(&app, &store, filter);
+            }
+            _ => {}
+        });
+    }
 
-                let input = slint_generatedMainWindow::ProductSearchInput {
-                    results: ModelRc::new(VecModel::from(modified_products)),
-                };
+    // Add Dealer Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_add_dealer(move |dealer_data, address_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
 
-                let app = weak_app.upgrade().unwrap();
-                app.set_input_data(slint_generatedMainWindow::Data {
-                    product_search_input: input,
+            // Only the country is prefilled, so anything else means an address was entered.
+            let address = address_from(&address_data);
+            let address = if address
+                == (Address {
+                    country: address.country.clone(),
                     ..Default::default()
-                });
+                }) {
+                None
+            } else {
+                let Some(address) = report(&app, address.normalized()) else {
+                    return;
+                };
+                Some(address)
+            };
+
+            let contacts: Vec<_> = dealer_data
+                .contacts
+                .iter()
+                .filter(|contact| !contact.phone_num.trim().is_empty())
+                .map(|contact| contact_from(&contact))
+                .collect();
+
+            let result = store.add_dealer(
+                dealer_data.first_name.as_str(),
+                match dealer_data.middle_name.as_str() {
+                    "" => None,
+                    middle_name => Some(middle_name),
+                },
+                dealer_data.last_name.as_str(),
+                &contacts,
+            );
+            let Some(dealer_id) = report(&app, result) else {
+                return;
+            };
+            if let Some(address) = address {
+                report(&app, store.add_dealer_address(dealer_id, &address));
             }
-            _ => {}
         });
     }
 
-    // Add Dealer Callback
+    // Dealer Selection Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_dealer_selected(move |dealer_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            show_dealer_detail(&app, &store.borrow(), dealer_data.dealer_id.into());
+        });
+    }
+
+    // Update Dealer Callback
     {
         let weak_store = Rc::downgrade(&store);
-        app.on_add_dealer(move |dealer_data| {
+        let weak_app = app.as_weak();
+        app.on_update_dealer(move |dealer_data| {
+            let app = weak_app.upgrade().unwrap();
             let store = weak_store.upgrade().unwrap();
             let mut store = store.borrow_mut();
-            store
-                .add_dealer(
-                    dealer_data.first_name.as_str(),
-                    match dealer_data.middle_name.as_str() {
-                        "" => None,
-                        middle_name => Some(middle_name),
-                    },
-                    dealer_data.last_name.as_str(),
-                    dealer_data.country_code.as_str(),
-                    dealer_data.phone_num.as_str(),
-                )
-                .unwrap();
+
+            let dealer_id = dealer_data.dealer_id.into();
+            let result = store.update_dealer(
+                dealer_id,
+                dealer_data.first_name.as_str(),
+                match dealer_data.middle_name.as_str() {
+                    "" => None,
+                    middle_name => Some(middle_name),
+                },
+                dealer_data.last_name.as_str(),
+            );
+            report(&app, result);
+            show_dealer_detail(&app, &store, dealer_id);
+        });
+    }
+
+    // Archive and Delete Dealer Callbacks
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_set_dealer_archived(move |dealer_data, archived| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let dealer_id = dealer_data.dealer_id.into();
+            let result = if archived {
+                store.archive_dealer(dealer_id)
+            } else {
+                store.restore_dealer(dealer_id)
+            };
+            report(&app, result);
+            show_dealer_detail(&app, &store, dealer_id);
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_delete_dealer(move |dealer_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+            report(&app, store.delete_dealer(dealer_data.dealer_id.into()));
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_show_archived_dealers(move |archived| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            show_dealers(&app, &store.borrow(), archived);
+        });
+    }
+
+    // Save Dealer Contact Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_save_dealer_contact(move |dealer_data, contact_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let dealer_id = dealer_data.dealer_id.into();
+            let contact = contact_from(&contact_data);
+            let result = if contact.phone_id == 0 {
+                store.add_dealer_contact(dealer_id, &contact).map(|_| ())
+            } else {
+                store.update_dealer_contact(dealer_id, &contact)
+            };
+            report(&app, result);
+            show_dealer_detail(&app, &store, dealer_id);
+        });
+    }
+
+    // Remove Dealer Contact Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_remove_dealer_contact(move |dealer_data, contact_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let dealer_id = dealer_data.dealer_id.into();
+            report(
+                &app,
+                store.remove_dealer_contact(dealer_id, contact_data.phone_id.into()),
+            );
+            show_dealer_detail(&app, &store, dealer_id);
+        });
+    }
+
+    // Primary Dealer Contact Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_make_primary_dealer_contact(move |dealer_data, contact_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let dealer_id = dealer_data.dealer_id.into();
+            report(
+                &app,
+                store.set_primary_contact(dealer_id, contact_data.phone_id.into()),
+            );
+            show_dealer_detail(&app, &store, dealer_id);
+        });
+    }
+
+    // Save Dealer Address Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_save_dealer_address(move |dealer_data, address_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let dealer_id = dealer_data.dealer_id.into();
+            let address = address_from(&address_data);
+            let result = if address.address_id == 0 {
+                store.add_dealer_address(dealer_id, &address).map(|_| ())
+            } else {
+                store.update_dealer_address(dealer_id, &address)
+            };
+            report(&app, result);
+            show_dealer_detail(&app, &store, dealer_data.dealer_id.into());
+        });
+    }
+
+    // Remove Dealer Address Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_remove_dealer_address(move |dealer_data, address_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let result = store.remove_dealer_address(
+                dealer_data.dealer_id.into(),
+                address_data.address_id.into(),
+            );
+            report(&app, result);
+            show_dealer_detail(&app, &store, dealer_data.dealer_id.into());
+        });
+    }
+
+    // Product Opened Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_product_opened(move |product_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            show_product_detail(&app, &store.borrow(), product_data.product_id.into());
+        });
+    }
+
+    // Update Product Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_update_product(move |product_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let product_id = product_data.product_id.into();
+            let result = store.update_product(
+                product_id,
+                product_data.product_name.as_str(),
+                product_data.brand_name.as_str(),
+                product_data.item_name.as_str(),
+                product_data.pack_name.as_str(),
+                Some(product_data.category_id.into()).filter(|id| *id != 0),
+            );
+            report(&app, result);
+            show_product_detail(&app, &store, product_id);
+        });
+    }
+
+    // Archive and Delete Product Callbacks
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_set_product_archived(move |product_data, archived| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let product_id = product_data.product_id.into();
+            let result = if archived {
+                store.archive_product(product_id)
+            } else {
+                store.restore_product(product_id)
+            };
+            report(&app, result);
+            show_product_detail(&app, &store, product_id);
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_delete_product(move |product_data, target| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let result = match target {
+                DeleteTarget::Product => store.delete_product(product_data.product_id.into()),
+                DeleteTarget::Brand => store.delete_brand(product_data.brand_id.into()),
+                DeleteTarget::Item => store.delete_item(product_data.item_id.into()),
+            };
+            report(&app, result);
+        });
+    }
+
+    // Link and Unlink Dealer Product Callbacks
+    {
+        // Both the dealer and the product dialog manage links; refresh whichever is open.
+        fn refresh(app: &MainWindow, store: &Store, dealer_id: i64, product_id: i64) {
+            match app.get_mode() {
+                Mode::DealerDetail => show_dealer_detail(app, store, dealer_id),
+                Mode::ProductDetail => show_product_detail(app, store, product_id),
+                _ => {}
+            }
+        }
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_link_dealer_product(move |dealer_data, product_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+            let (dealer_id, product_id) =
+                (dealer_data.dealer_id.into(), product_data.product_id.into());
+
+            report(&app, store.link_dealer_product(dealer_id, product_id));
+            refresh(&app, &store, dealer_id, product_id);
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_unlink_dealer_product(move |dealer_data, product_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+            let (dealer_id, product_id) =
+                (dealer_data.dealer_id.into(), product_data.product_id.into());
+
+            report(&app, store.unlink_dealer_product(dealer_id, product_id));
+            refresh(&app, &store, dealer_id, product_id);
+        });
+    }
+
+    // Alias Callbacks
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_add_product_alias(move |product_data, name, target| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+            let product_id = product_data.product_id.into();
+            let target = match target {
+                AliasFor::Product => AliasTarget::Product(product_id),
+                AliasFor::Brand => AliasTarget::Brand(product_data.brand_id.into()),
+                AliasFor::Item => AliasTarget::Item(product_data.item_id.into()),
+            };
+
+            report(&app, store.add_alias(name.as_str(), target));
+            show_product_detail(&app, &store, product_id);
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_delete_product_alias(move |product_data, alias_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            report(&app, store.delete_alias(alias_data.alias_id.into()));
+            show_product_detail(&app, &store, product_data.product_id.into());
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_add_starter_aliases(move |product_data| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            report(&app, store.add_starter_aliases());
+            show_product_detail(&app, &store, product_data.product_id.into());
+        });
+    }
+
+    // Merge Duplicate Callbacks
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_merge_products(move |keep, duplicate| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let product_id = keep.product_id.into();
+            report(
+                &app,
+                store.merge_products(product_id, duplicate.product_id.into()),
+            );
+            show_product_detail(&app, &store, product_id);
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_merge_dealers(move |keep, duplicate| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+
+            let dealer_id = keep.dealer_id.into();
+            report(
+                &app,
+                store.merge_dealers(dealer_id, duplicate.dealer_id.into()),
+            );
+            show_dealer_detail(&app, &store, dealer_id);
         });
     }
 
     // Add Product Callback
     {
         let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
         app.on_add_product(move |product_data| {
+            let app = weak_app.upgrade().unwrap();
             let store = weak_store.upgrade().unwrap();
             let mut store = store.borrow_mut();
-            store
-                .add_product(
-                    product_data.product_name.as_str(),
-                    product_data.brand_name.as_str(),
-                    product_data.item_name.as_str(),
-                    product_data.pack_name.as_str(),
-                )
-                .unwrap();
+            let result = store.add_product(
+                product_data.product_name.as_str(),
+                product_data.brand_name.as_str(),
+                product_data.item_name.as_str(),
+                product_data.pack_name.as_str(),
+                Some(product_data.category_id.into()).filter(|id| *id != 0),
+            );
+            report(&app, result);
+        });
+    }
+
+    // Product Filter Callbacks
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_category_selected(move |category_id| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut filter = app.get_input_data().product_search_input.filter;
+            filter.category_id = category_id;
+            show_products(&app, &store.borrow(), filter);
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_show_archived_products(move |archived| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut filter = app.get_input_data().product_search_input.filter;
+            filter.archived = archived;
+            show_products(&app, &store.borrow(), filter);
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_show_unparsed_pack_products(move |unparsed_packs| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut filter = app.get_input_data().product_search_input.filter;
+            filter.unparsed_packs = unparsed_packs;
+            show_products(&app, &store.borrow(), filter);
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_brand_selected(move |brand_id| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut filter = app.get_input_data().product_search_input.filter;
+            filter.brand_id = brand_id;
+            show_products(&app, &store.borrow(), filter);
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_item_selected(move |item_id| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut filter = app.get_input_data().product_search_input.filter;
+            filter.item_id = item_id;
+            show_products(&app, &store.borrow(), filter);
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_product_sort_selected(move |sort| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut filter = app.get_input_data().product_search_input.filter;
+            filter.sort = sort;
+            show_products(&app, &store.borrow(), filter);
+        });
+    }
+
+    // Add Category Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_add_category(move |name, parent_id| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+            let parent_id = Some(parent_id.into()).filter(|id| *id != 0);
+            if let Some(category_id) = report(&app, store.add_category(&name, parent_id)) {
+                let Some(category_id) = report(&app, slint_id(category_id)) else {
+                    return;
+                };
+                let mut filter = app.get_input_data().product_search_input.filter;
+                filter.category_id = category_id;
+                show_products(&app, &store, filter);
+            }
         });
     }
 
@@ -166,90 +1079,105 @@ fn main() {
         let weak_store = Rc::downgrade(&store);
         app.on_product_selected(move |product_data, mode| match mode {
             Mode::ComparisonTable => {
+                let app = weak_app.upgrade().unwrap();
                 let store = weak_store.upgrade().unwrap();
-                let store = store.borrow();
+                let mut store = store.borrow_mut();
+                report(
+                    &app,
+                    store.record_product_selection(
+                        SearchContext::Comparison,
+                        product_data.product_id.into(),
+                    ),
+                );
 
-                let price_pairs = store
-                    .get_latest_dealer_price_pairs_for(Product {
-                        product_name: product_data.product_name.to_string(),
-                        brand_name: product_data.brand_name.to_string(),
-                        item_name: product_data.item_name.to_string(),
-                        pack_name: product_data.pack_name.to_string(),
-                    })
-                    .unwrap();
-
-                let price_pairs: Vec<_> = price_pairs
-                    .into_iter()
-                    .map(|price_pair| slint_generatedMainWindow::DealerPricePair {
-                        dealer: slint_generatedMainWindow::DealerData {
-                            first_name: price_pair.0.first_name.to_shared_string(),
-                            middle_name: price_pair
-                                .0
-                                .middle_name
-                                .unwrap_or_default()
-                                .to_shared_string(),
-                            last_name: price_pair.0.last_name.to_shared_string(),
-                            phone_num: price_pair.0.phone_num.to_shared_string(),
-                            ..Default::default()
-                        },
-                        price: price_pair.1 as i32,
+                let Some(price_pairs) = report(
+                    &app,
+                    store.get_latest_dealer_price_pairs_for(product_data.product_id.into()),
+                ) else {
+                    return;
+                };
+                let Some(product) = report(&app, store.get_product(product_data.product_id.into()))
+                else {
+                    return;
+                };
+
+                let price_pairs = data_model(price_pairs, |(dealer, price)| {
+                    Ok(slint_generatedMainWindow::DealerPricePair {
+                        dealer: dealer_data(dealer)?,
+                        price: price.to_shared_string(),
+                        unit_price: product
+                            .pack
+                            .map(|pack| pack.unit_price(price).to_shared_string())
+                            .unwrap_or_default(),
                     })
-                    .collect();
+                });
+                let Some(price_pairs) = report(&app, price_pairs) else {
+                    return;
+                };
+
+                let Some(offers) = report(&app, store.get_item_comparison(product.item_id))
+                else {
+                    return;
+                };
+                let Some(offers) = report(&app, data_model(offers, item_offer_data)) else {
+                    return;
+                };
 
                 let dealers_conn = slint_generatedMainWindow::ProductDealersConnection {
                     product: product_data,
-                    dealer_price_pairs: slint::ModelRc::from(Rc::new(slint::VecModel::from(
-                        price_pairs,
-                    ))),
+                    dealer_price_pairs: price_pairs,
                 };
 
                 let input = slint_generatedMainWindow::ComparisonTableInput {
                     product_dealers_connection: dealers_conn,
+                    item_offers: offers,
                 };
 
-                let app = weak_app.upgrade().unwrap();
                 app.set_input_data(slint_generatedMainWindow::Data {
                     comparison_table_input: input,
                     ..Default::default()
                 });
             }
             Mode::DataFeedProcedure => {
+                let app = weak_app.upgrade().unwrap();
                 let store = weak_store.upgrade().unwrap();
-                let store = store.borrow();
+                let mut store = store.borrow_mut();
+                report(
+                    &app,
+                    store.record_product_selection(
+                        SearchContext::DataFeed,
+                        product_data.product_id.into(),
+                    ),
+                );
 
-                let price_pairs = store
-                    .get_latest_dealer_price_pairs_for(Product {
-                        product_name: product_data.product_name.to_string(),
-                        brand_name: product_data.brand_name.to_string(),
-                        item_name: product_data.item_name.to_string(),
-                        pack_name: product_data.pack_name.to_string(),
-                    })
-                    .unwrap();
-
-                let price_pairs: Vec<_> = price_pairs
-                    .into_iter()
-                    .map(|price_pair| slint_generatedMainWindow::DealerPricePair {
-                        dealer: slint_generatedMainWindow::DealerData {
-                            first_name: price_pair.0.first_name.to_shared_string(),
-                            middle_name: price_pair
-                                .0
-                                .middle_name
-                                .unwrap_or_default()
-                                .to_shared_string(),
-                            last_name: price_pair.0.last_name.to_shared_string(),
-                            country_code: price_pair.0.country_code.to_shared_string(),
-                            phone_num: price_pair.0.phone_num.to_shared_string(),
-                            ..Default::default()
-                        },
-                        price: price_pair.1 as i32,
+                let Some(price_pairs) = report(
+                    &app,
+                    store.get_dealer_price_pairs_for(product_data.product_id.into()),
+                ) else {
+                    return;
+                };
+                if price_pairs.is_empty() {
+                    app.set_error_message(
+                        "No dealer stocks this product yet. Link dealers to it from the Products page."
+                            .into(),
+                    );
+                }
+
+                // Dealers who have not quoted yet get an empty price.
+                let price_pairs = data_model(price_pairs, |(dealer, price)| {
+                    Ok(slint_generatedMainWindow::DealerPricePair {
+                        dealer: dealer_data(dealer)?,
+                        price: price.map(|price| price.to_shared_string()).unwrap_or_default(),
+                        ..Default::default()
                     })
-                    .collect();
+                });
+                let Some(price_pairs) = report(&app, price_pairs) else {
+                    return;
+                };
 
                 let dealers_conn = slint_generatedMainWindow::ProductDealersConnection {
                     product: product_data,
-                    dealer_price_pairs: slint::ModelRc::from(Rc::new(slint::VecModel::from(
-                        price_pairs,
-                    ))),
+                    dealer_price_pairs: price_pairs,
                 };
 
                 let input = slint_generatedMainWindow::DataFeedInput {
@@ -257,7 +1185,6 @@ fn main() {
                     index: 0,
                 };
 
-                let app = weak_app.upgrade().unwrap();
                 app.set_input_data(slint_generatedMainWindow::Data {
                     data_feed_input: input,
                     ..Default::default()
@@ -267,54 +1194,111 @@ fn main() {
         });
     }
 
-    // Data Feed Increment Callback
+    // Clear Recent Products Callback
     {
+        let weak_store = Rc::downgrade(&store);
         let weak_app = app.as_weak();
-        app.on_increment_data_feed(move || {
+        app.on_clear_recent_products(move |mode| {
             let app = weak_app.upgrade().unwrap();
-            {
-                let input_data = app.get_input_data();
-                let data_feed_input = input_data.data_feed_input;
-
-                app.set_input_data(slint_generatedMainWindow::Data {
-                    data_feed_input: slint_generatedMainWindow::DataFeedInput {
-                        product_dealers_connection: data_feed_input.product_dealers_connection,
-                        index: data_feed_input.index + 1,
-                    },
-                    ..Default::default()
-                });
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+            if let Some(context) = search_context(mode) {
+                if report(&app, store.clear_recent_products(context)).is_some() {
+                    show_product_selection(&app, &store, "", context);
+                }
             }
+        });
+    }
 
+    // Data Feed Increment Callback
+    {
+        let weak_app = app.as_weak();
+        app.on_increment_data_feed(move || {
+            let app = weak_app.upgrade().unwrap();
             let input_data = app.get_input_data();
             let data_feed_input = input_data.data_feed_input;
+
+            app.set_input_data(slint_generatedMainWindow::Data {
+                data_feed_input: slint_generatedMainWindow::DataFeedInput {
+                    product_dealers_connection: data_feed_input.product_dealers_connection,
+                    index: data_feed_input.index + 1,
+                },
+                ..Default::default()
+            });
         });
     }
 
     // Price Changed Callback
     {
         let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
         app.on_price_changed(move |product, dealer, price| {
-            let product = Product {
-                item_name: product.item_name.to_string(),
-                brand_name: product.brand_name.to_string(),
-                product_name: product.product_name.to_string(),
-                pack_name: product.pack_name.to_string(),
-            };
-            let dealer = Dealer {
-                first_name: dealer.first_name.to_string(),
-                middle_name: if dealer.middle_name.is_empty() {
-                    None
-                } else {
-                    Some(dealer.middle_name.to_string())
-                },
-                last_name: dealer.last_name.to_string(),
-                country_code: dealer.country_code.to_string(),
-                phone_num: dealer.phone_num.to_string(),
+            let app = weak_app.upgrade().unwrap();
+            let Some(price) = report(&app, price.parse::<Money>()) else {
+                return;
             };
 
             let store = weak_store.upgrade().unwrap();
             let store = store.borrow();
-            store.update_price(product, dealer, price.parse().unwrap());
+            report(
+                &app,
+                store.update_price(product.product_id.into(), dealer.dealer_id.into(), price),
+            );
+        });
+    }
+
+    // Export Callback
+    {
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        let directory = path.with_file_name("export");
+        app.on_export_data(move || {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let store = store.borrow();
+            if report(&app, store.export_to(&directory)).is_some() {
+                app.set_notice_message(
+                    format!(
+                        "Products, dealers and prices were written to {}",
+                        directory.display()
+                    )
+                    .into(),
+                );
+            }
+        });
+    }
+
+    // Search Ranking Callbacks
+    {
+        if let Some(weighting) = report(&app, store.borrow().selection_weighting()) {
+            app.set_selection_weighting(selection_weighting_data(weighting));
+        }
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_save_selection_weighting(move |max_points, half_life_days| {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            let mut store = store.borrow_mut();
+            let result = selection_weighting_from(&max_points, &half_life_days)
+                .and_then(|weighting| store.set_selection_weighting(weighting))
+                .and_then(|()| store.selection_weighting());
+            if let Some(weighting) = report(&app, result) {
+                app.set_selection_weighting(selection_weighting_data(weighting));
+                app.set_notice_message("Search ranking saved".into());
+            }
+        });
+
+        let weak_store = Rc::downgrade(&store);
+        let weak_app = app.as_weak();
+        app.on_reset_selection_boosts(move || {
+            let app = weak_app.upgrade().unwrap();
+            let store = weak_store.upgrade().unwrap();
+            if report(&app, store.borrow_mut().reset_selection_boosts()).is_some() {
+                app.set_notice_message(
+                    "Products picked before no longer rank higher in searches".into(),
+                );
+            }
         });
     }
 
@@ -0,0 +1,472 @@
+use crate::{delete_if_unused, tidy_name, Dealer, Product, Result, Store, StoreError};
+use levenshtein::levenshtein;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+
+impl Store {
+    /// Pairs of active products that look like one product entered twice,
+    /// the older of each pair first. See `same_product`.
+    pub fn find_duplicate_products(&self) -> Result<Vec<(Product, Product)>> {
+        let mut by_pack: HashMap<String, Vec<Product>> = HashMap::new();
+        for product in self.get_products()? {
+            by_pack.entry(pack_key(&product)).or_default().push(product);
+        }
+
+        let mut pairs = Vec::new();
+        for products in by_pack.values_mut() {
+            products.sort_by_key(|product| product.product_id);
+            for (i, older) in products.iter().enumerate() {
+                for newer in &products[i + 1..] {
+                    if same_product(older, newer) {
+                        pairs.push((older.clone(), newer.clone()));
+                    }
+                }
+            }
+        }
+        pairs.sort_by_key(|(older, newer)| (older.product_id, newer.product_id));
+        Ok(pairs)
+    }
+
+    /// Pairs of active dealers that look like one dealer entered twice, the
+    /// older of each pair first. See `same_dealer`.
+    pub fn find_duplicate_dealers(&self) -> Result<Vec<(Dealer, Dealer)>> {
+        let mut dealers = self.get_dealers()?;
+        dealers.sort_by_key(|dealer| dealer.dealer_id);
+
+        let mut pairs = Vec::new();
+        for (i, older) in dealers.iter().enumerate() {
+            for newer in &dealers[i + 1..] {
+                if same_dealer(older, newer) {
+                    pairs.push((older.clone(), newer.clone()));
+                }
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// The active products that look like duplicates of this one.
+    pub fn get_product_duplicates(&self, product_id: i64) -> Result<Vec<Product>> {
+        let product = self.get_product(product_id)?;
+        Ok(self
+            .get_products()?
+            .into_iter()
+            .filter(|other| other.product_id != product_id && same_product(&product, other))
+            .collect())
+    }
+
+    /// The active dealers that look like duplicates of this one.
+    pub fn get_dealer_duplicates(&self, dealer_id: i64) -> Result<Vec<Dealer>> {
+        let dealer = self.get_dealer(dealer_id)?;
+        Ok(self
+            .get_dealers()?
+            .into_iter()
+            .filter(|other| other.dealer_id != dealer_id && same_dealer(&dealer, other))
+            .collect())
+    }
+
+    /// Moves the duplicate's prices, dealer links, aliases and search history
+    /// onto `keep_id` and deletes the duplicate. Where both have a price
+    /// recorded at the same moment, `keep_id`'s stays.
+    pub fn merge_products(&mut self, keep_id: i64, duplicate_id: i64) -> Result<()> {
+        self.ensure_mergeable("product", keep_id, duplicate_id)?;
+
+        let transaction = self.connection.transaction()?;
+
+        let (item_id, brand_id): (i64, i64) = transaction.query_row(
+            "SELECT item_id, brand_id FROM product WHERE product_id = ?1",
+            params![duplicate_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        transaction.execute_batch(&format!(
+            "
+            INSERT OR IGNORE INTO dealer_price (product_id, dealer_id, price_paise, time_stamp)
+            SELECT {keep_id}, dealer_id, price_paise, time_stamp
+            FROM dealer_price WHERE product_id = {duplicate_id};
+
+            INSERT OR IGNORE INTO dealer_product (dealer_id, product_id)
+            SELECT dealer_id, {keep_id} FROM dealer_product WHERE product_id = {duplicate_id};
+
+            INSERT INTO recent_product (context, product_id, recency)
+            SELECT context, {keep_id}, recency FROM recent_product WHERE product_id = {duplicate_id}
+            ON CONFLICT (context, product_id) DO UPDATE
+            SET recency = MAX(recency, excluded.recency);
+
+            INSERT INTO product_selection (product_id, weight, selected_at)
+            SELECT {keep_id}, weight, selected_at FROM product_selection
+            WHERE product_id = {duplicate_id}
+            ON CONFLICT (product_id) DO UPDATE
+            SET weight = excluded.weight, selected_at = excluded.selected_at
+            WHERE excluded.weight > product_selection.weight;
+
+            UPDATE OR IGNORE alias SET product_id = {keep_id} WHERE product_id = {duplicate_id};
+
+            DELETE FROM product WHERE product_id = {duplicate_id};
+            "
+        ))?;
+        delete_if_unused(&transaction, item_id, brand_id)?;
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Moves the duplicate's prices, product links, addresses and phone
+    /// numbers onto `keep_id` and deletes the duplicate. Numbers `keep_id`
+    /// already has are dropped, and `keep_id`'s primary number stays primary.
+    pub fn merge_dealers(&mut self, keep_id: i64, duplicate_id: i64) -> Result<()> {
+        self.ensure_mergeable("dealer", keep_id, duplicate_id)?;
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute_batch(&format!(
+            "
+            UPDATE dealer
+            SET middle_name = COALESCE(middle_name,
+                (SELECT middle_name FROM dealer WHERE dealer_id = {duplicate_id}))
+            WHERE dealer_id = {keep_id};
+
+            INSERT OR IGNORE INTO dealer_price (product_id, dealer_id, price_paise, time_stamp)
+            SELECT product_id, {keep_id}, price_paise, time_stamp
+            FROM dealer_price WHERE dealer_id = {duplicate_id};
+
+            INSERT OR IGNORE INTO dealer_product (dealer_id, product_id)
+            SELECT {keep_id}, product_id FROM dealer_product WHERE dealer_id = {duplicate_id};
+
+            INSERT OR IGNORE INTO dealer_location (dealer_id, address_id, description)
+            SELECT {keep_id}, address_id, description
+            FROM dealer_location WHERE dealer_id = {duplicate_id};
+
+            INSERT OR IGNORE INTO dealer_contact (dealer_id, phone_id, description, is_primary)
+            SELECT {keep_id}, dc.phone_id, dc.description, 0
+            FROM dealer_contact dc
+            JOIN phone p ON p.phone_id = dc.phone_id
+            WHERE dc.dealer_id = {duplicate_id}
+            AND NOT EXISTS (
+                SELECT 1 FROM dealer_contact kept
+                JOIN phone kept_phone ON kept_phone.phone_id = kept.phone_id
+                WHERE kept.dealer_id = {keep_id}
+                AND kept_phone.country_code = p.country_code
+                AND kept_phone.phone_number = p.phone_number
+            );
+
+            UPDATE dealer_contact SET is_primary = 1
+            WHERE dealer_id = {keep_id}
+            AND NOT EXISTS (SELECT 1 FROM dealer_contact WHERE dealer_id = {keep_id} AND is_primary)
+            AND phone_id = (SELECT MIN(phone_id) FROM dealer_contact WHERE dealer_id = {keep_id});
+
+            DELETE FROM dealer WHERE dealer_id = {duplicate_id};
+            DELETE FROM phone WHERE phone_id NOT IN (SELECT phone_id FROM dealer_contact);
+            DELETE FROM address WHERE address_id NOT IN (SELECT address_id FROM dealer_location);
+            "
+        ))?;
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Files every product and alias of the duplicate brand under `keep_id`
+    /// and deletes the duplicate.
+    pub fn merge_brands(&mut self, keep_id: i64, duplicate_id: i64) -> Result<()> {
+        self.ensure_mergeable("brand", keep_id, duplicate_id)?;
+        let transaction = self.connection.transaction()?;
+        move_brand(&transaction, keep_id, duplicate_id)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Files every product and alias of the duplicate item under `keep_id`
+    /// and deletes the duplicate. `keep_id` takes the duplicate's category if
+    /// it has none.
+    pub fn merge_items(&mut self, keep_id: i64, duplicate_id: i64) -> Result<()> {
+        self.ensure_mergeable("item", keep_id, duplicate_id)?;
+        let transaction = self.connection.transaction()?;
+        move_item(&transaction, keep_id, duplicate_id)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn ensure_mergeable(&self, table: &'static str, keep_id: i64, duplicate_id: i64) -> Result<()> {
+        self.ensure_exists(table, keep_id)?;
+        self.ensure_exists(table, duplicate_id)?;
+        if keep_id == duplicate_id {
+            return Err(StoreError::validation(format!(
+                "A {table} cannot be merged into itself"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Points the duplicate brand's products and aliases at `keep_id` and
+/// deletes it. Aliases `keep_id` already has are dropped.
+pub(crate) fn move_brand(
+    connection: &Connection,
+    keep_id: i64,
+    duplicate_id: i64,
+) -> rusqlite::Result<()> {
+    connection.execute_batch(&format!(
+        "
+        UPDATE product SET brand_id = {keep_id} WHERE brand_id = {duplicate_id};
+        UPDATE OR IGNORE alias SET brand_id = {keep_id} WHERE brand_id = {duplicate_id};
+        DELETE FROM alias WHERE brand_id = {duplicate_id};
+        DELETE FROM brand WHERE brand_id = {duplicate_id};
+        "
+    ))
+}
+
+/// Like `move_brand`, for items. `keep_id` takes the duplicate's category if
+/// it has none.
+pub(crate) fn move_item(
+    connection: &Connection,
+    keep_id: i64,
+    duplicate_id: i64,
+) -> rusqlite::Result<()> {
+    connection.execute_batch(&format!(
+        "
+        UPDATE item
+        SET category_id = COALESCE(category_id,
+            (SELECT category_id FROM item WHERE item_id = {duplicate_id}))
+        WHERE item_id = {keep_id};
+        UPDATE product SET item_id = {keep_id} WHERE item_id = {duplicate_id};
+        UPDATE OR IGNORE alias SET item_id = {keep_id} WHERE item_id = {duplicate_id};
+        DELETE FROM alias WHERE item_id = {duplicate_id};
+        DELETE FROM item WHERE item_id = {duplicate_id};
+        "
+    ))
+}
+
+/// Two products are taken for one when their packs are the same size and
+/// their items, and their brands and names read together, are the same give
+/// or take a typo.
+fn same_product(a: &Product, b: &Product) -> bool {
+    pack_key(a) == pack_key(b)
+        && near(&name_key(&a.item_name), &name_key(&b.item_name))
+        && near(
+            &name_key(&format!("{} {}", a.brand_name, a.product_name)),
+            &name_key(&format!("{} {}", b.brand_name, b.product_name)),
+        )
+}
+
+/// Two dealers are taken for one when they share a phone number, or when
+/// their first and last names are the same give or take a typo.
+fn same_dealer(a: &Dealer, b: &Dealer) -> bool {
+    let full_name =
+        |dealer: &Dealer| name_key(&format!("{} {}", dealer.first_name, dealer.last_name));
+    a.contacts.iter().any(|contact| {
+        b.contacts
+            .iter()
+            .any(|other| other.e164() == contact.e164())
+    }) || near(&full_name(a), &full_name(b))
+}
+
+/// Equal for packs of the same size, so "1kg" matches "1000 g".
+fn pack_key(product: &Product) -> String {
+    match product.pack {
+        Some(pack) => format!("{:?} {:.3}", pack.measure(), pack.base_amount()),
+        None => name_key(&product.pack_name),
+    }
+}
+
+fn name_key(name: &str) -> String {
+    tidy_name(name).to_lowercase()
+}
+
+/// Whether `a` and `b` differ by no more than a typo: one edit for every
+/// four letters of the shorter, and two at most.
+fn near(a: &str, b: &str) -> bool {
+    let allowed = (a.chars().count().min(b.chars().count()) / 4).min(2);
+    levenshtein(a, b) <= allowed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Address, AliasTarget, Contact, Money, SearchContext};
+    use std::path::Path;
+
+    fn number(phone_number: &str) -> Contact {
+        Contact {
+            country_code: "+91".to_string(),
+            phone_number: phone_number.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn duplicate_products_are_found_and_merged() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let typo = store
+            .add_product("Buter", "Amul", "Butter", "0.5 kg", None)
+            .unwrap();
+        let larger = store
+            .add_product("Butter", "Amul", "Butter", "1kg", None)
+            .unwrap();
+        let cheese = store
+            .add_product("Cheese", "Amul", "Cheese", "500g", None)
+            .unwrap();
+
+        let pairs: Vec<_> = store
+            .find_duplicate_products()
+            .unwrap()
+            .iter()
+            .map(|(older, newer)| (older.product_id, newer.product_id))
+            .collect();
+        assert_eq!(pairs, vec![(butter, typo)]);
+        let duplicates: Vec<_> = store
+            .get_product_duplicates(typo)
+            .unwrap()
+            .iter()
+            .map(|product| product.product_id)
+            .collect();
+        assert_eq!(duplicates, vec![butter]);
+
+        let ramesh = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+        let suresh = store.add_dealer("Suresh", None, "Jain", &[]).unwrap();
+        store
+            .update_price_at(
+                butter,
+                ramesh,
+                Money::from_rupees(250),
+                "2024-01-01 10:00:00",
+            )
+            .unwrap();
+        store
+            .update_price_at(typo, ramesh, Money::from_rupees(260), "2024-02-01 10:00:00")
+            .unwrap();
+        store
+            .update_price_at(typo, suresh, Money::from_rupees(255), "2024-02-01 10:00:00")
+            .unwrap();
+        store
+            .add_alias("makhan", AliasTarget::Product(typo))
+            .unwrap();
+        store
+            .record_product_selection(SearchContext::Comparison, typo)
+            .unwrap();
+
+        store.merge_products(butter, typo).unwrap();
+
+        assert!(matches!(
+            store.get_product(typo),
+            Err(StoreError::NotFound("product"))
+        ));
+        assert_eq!(store.get_price_history(butter, ramesh).unwrap().len(), 2);
+        assert_eq!(store.get_product_dealers(butter).unwrap().len(), 2);
+        assert_eq!(store.get_product_aliases(butter).unwrap().len(), 1);
+        let recent: Vec<_> = store
+            .get_recent_product_results(SearchContext::Comparison)
+            .unwrap()
+            .iter()
+            .map(|product| product.product_id)
+            .collect();
+        assert_eq!(recent, vec![butter]);
+        assert!(store.find_duplicate_products().unwrap().is_empty());
+        assert_eq!(store.get_product(larger).unwrap().product_id, larger);
+        assert_eq!(store.get_product(cheese).unwrap().product_id, cheese);
+
+        assert!(matches!(
+            store.merge_products(butter, butter),
+            Err(StoreError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn duplicate_dealers_are_found_and_merged() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let ramesh = store
+            .add_dealer("Ramesh", None, "Gupta", &[number("9876543210")])
+            .unwrap();
+        let typo = store
+            .add_dealer(
+                "Ramesh",
+                Some("Kumar"),
+                "Gupt",
+                &[number("9876500000"), number("9876543210")],
+            )
+            .unwrap();
+        let same_number = store
+            .add_dealer("RK", None, "Traders", &[number("9876500000")])
+            .unwrap();
+        let suresh = store.add_dealer("Suresh", None, "Jain", &[]).unwrap();
+
+        let pairs: Vec<_> = store
+            .find_duplicate_dealers()
+            .unwrap()
+            .iter()
+            .map(|(older, newer)| (older.dealer_id, newer.dealer_id))
+            .collect();
+        assert_eq!(pairs, vec![(ramesh, typo), (typo, same_number)]);
+
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        store
+            .update_price(butter, typo, Money::from_rupees(250))
+            .unwrap();
+        let address = Address {
+            city_name: "Jaipur".to_string(),
+            district_name: "Jaipur".to_string(),
+            pin_code: "302001".to_string(),
+            state: "Rajasthan".to_string(),
+            country: "India".to_string(),
+            ..Default::default()
+        };
+        store.add_dealer_address(typo, &address).unwrap();
+
+        store.merge_dealers(ramesh, typo).unwrap();
+
+        let merged = store.get_dealer(ramesh).unwrap();
+        assert_eq!(merged.middle_name.as_deref(), Some("Kumar"));
+        let numbers: Vec<_> = merged
+            .contacts
+            .iter()
+            .map(|contact| (contact.phone_number.as_str(), contact.is_primary))
+            .collect();
+        assert_eq!(numbers, vec![("9876543210", true), ("9876500000", false)]);
+        assert_eq!(store.get_dealer_addresses(ramesh).unwrap().len(), 1);
+        assert_eq!(store.get_dealer_products(ramesh).unwrap().len(), 1);
+        assert!(matches!(
+            store.get_dealer(typo),
+            Err(StoreError::NotFound("dealer"))
+        ));
+        let pairs: Vec<_> = store
+            .find_duplicate_dealers()
+            .unwrap()
+            .iter()
+            .map(|(older, newer)| (older.dealer_id, newer.dealer_id))
+            .collect();
+        assert_eq!(pairs, vec![(ramesh, same_number)]);
+        assert!(store.get_dealer_duplicates(suresh).unwrap().is_empty());
+    }
+
+    #[test]
+    fn brands_and_items_merge() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let amul = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let typo = store
+            .add_product("Ghee", "Amull", "Clarified Butter", "1L", None)
+            .unwrap();
+        let amul = store.get_product(amul).unwrap();
+        let typo = store.get_product(typo).unwrap();
+        store
+            .add_alias("Amul Dairy", AliasTarget::Brand(typo.brand_id))
+            .unwrap();
+
+        store.merge_brands(amul.brand_id, typo.brand_id).unwrap();
+        store.merge_items(amul.item_id, typo.item_id).unwrap();
+
+        let ghee = store.get_product(typo.product_id).unwrap();
+        assert_eq!(ghee.brand_name, "Amul");
+        assert_eq!(ghee.item_name, "Butter");
+        assert_eq!(store.get_brands().unwrap().len(), 1);
+        assert_eq!(store.get_items().unwrap().len(), 1);
+        assert_eq!(store.get_product_aliases(amul.product_id).unwrap().len(), 1);
+        assert!(matches!(
+            store.merge_brands(amul.brand_id, typo.brand_id),
+            Err(StoreError::NotFound("brand"))
+        ));
+    }
+}
@@ -0,0 +1,1060 @@
+use crate::error::{Result, StoreError};
+use phonenumber::metadata::DATABASE;
+use phonenumber::{country, Mode, PhoneNumber};
+use rusqlite::{params, Connection, Transaction};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/// One step of the schema history. Steps are applied in order, each inside
+/// its own transaction, and the database's `PRAGMA user_version` records how
+/// many of them have been applied.
+pub(crate) struct Migration {
+    pub description: &'static str,
+    pub apply: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// Never reorder or edit an entry once it has shipped; append a new one instead.
+/// Steps only call code in this file, so later changes to the store cannot
+/// change what an old step does.
+pub(crate) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "initial schema",
+        apply: initial_schema,
+    },
+    Migration {
+        description: "primary dealer contacts",
+        apply: primary_dealer_contacts,
+    },
+    Migration {
+        description: "normalize phone numbers",
+        apply: normalize_phone_numbers,
+    },
+    Migration {
+        description: "prices in paise",
+        apply: prices_in_paise,
+    },
+    Migration {
+        description: "recent products and settings",
+        apply: recent_products_and_settings,
+    },
+    Migration {
+        description: "dealer product links",
+        apply: dealer_product_links,
+    },
+    Migration {
+        description: "archived products and dealers",
+        apply: archived_products_and_dealers,
+    },
+    Migration {
+        description: "product search index",
+        apply: product_search_index,
+    },
+    Migration {
+        description: "aliases",
+        apply: aliases,
+    },
+    Migration {
+        description: "selection boosts",
+        apply: selection_boosts,
+    },
+    Migration {
+        description: "structured packs",
+        apply: structured_packs,
+    },
+    Migration {
+        description: "case-insensitive brand and item names",
+        apply: case_insensitive_names,
+    },
+];
+
+/// The schema version this binary writes and understands.
+pub const CURRENT_VERSION: u32 = MIGRATIONS.len() as u32;
+
+pub fn schema_version(connection: &Connection) -> rusqlite::Result<u32> {
+    connection.query_row("PRAGMA user_version", (), |row| row.get(0))
+}
+
+/// Brings the database up to `CURRENT_VERSION`.
+pub(crate) fn migrate(connection: &mut Connection) -> Result<()> {
+    migrate_to(connection, CURRENT_VERSION)
+}
+
+pub(crate) fn migrate_to(connection: &mut Connection, target: u32) -> Result<()> {
+    let mut version = schema_version(connection)?;
+
+    if version > CURRENT_VERSION {
+        return Err(StoreError::SchemaTooNew {
+            found: version,
+            supported: CURRENT_VERSION,
+        });
+    }
+
+    // Databases created before versioning have the initial schema but a
+    // user_version of 0.
+    if version == 0 && has_table(connection, "dealer")? {
+        connection.pragma_update(None, "user_version", 1)?;
+        version = 1;
+    }
+
+    // Table rebuilds in later steps must not trip foreign key actions halfway
+    // through, so enforcement is off while migrating and checked per step.
+    connection.pragma_update(None, "foreign_keys", false)?;
+
+    for (index, migration) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .take(target as usize)
+        .skip(version as usize)
+    {
+        let next = index as u32 + 1;
+        let transaction = connection.transaction()?;
+        (migration.apply)(&transaction)
+            .and_then(|_| check_foreign_keys(&transaction))
+            .map_err(|source| StoreError::MigrationFailed {
+                version: next,
+                description: migration.description,
+                source,
+            })?;
+        transaction.pragma_update(None, "user_version", next)?;
+        transaction.commit()?;
+    }
+
+    connection.pragma_update(None, "foreign_keys", true)?;
+    Ok(())
+}
+
+fn has_table(connection: &Connection, name: &str) -> rusqlite::Result<bool> {
+    connection.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [name],
+        |row| row.get(0),
+    )
+}
+
+fn check_foreign_keys(transaction: &Transaction) -> rusqlite::Result<()> {
+    let violation = transaction
+        .prepare("PRAGMA foreign_key_check")?
+        .query(())?
+        .next()?
+        .is_some();
+
+    if violation {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY),
+            Some("migration left dangling foreign keys".to_string()),
+        ));
+    }
+    Ok(())
+}
+
+fn initial_schema(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE category (
+            category_id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL CHECK (LENGTH(name) > 0),
+            parent_id INT,
+            FOREIGN KEY (parent_id) REFERENCES category(category_id) ON DELETE SET NULL
+        );
+
+        CREATE TABLE item (
+            item_id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL CHECK (LENGTH(name) > 0),
+            category_id INT DEFAULT NULL,
+            FOREIGN KEY (category_id) REFERENCES category(category_id) ON DELETE SET NULL
+        );
+
+        CREATE TABLE brand (
+            brand_id INTEGER PRIMARY KEY,
+            name TEXT UNIQUE NOT NULL CHECK (LENGTH(name) > 0)
+        );
+
+        CREATE TABLE product (
+            product_id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL CHECK (LENGTH(name) > 0),
+            pack_name TEXT NOT NULL CHECK (LENGTH(pack_name) > 0),
+            item_id INT NOT NULL,
+            brand_id INT NOT NULL,
+            FOREIGN KEY (item_id) REFERENCES item(item_id) ON DELETE CASCADE,
+            FOREIGN KEY (brand_id) REFERENCES brand(brand_id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE phone (
+            phone_id INTEGER PRIMARY KEY,
+            country_code TEXT NOT NULL CHECK (LENGTH(country_code) > 0),
+            phone_number TEXT NOT NULL CHECK (LENGTH(phone_number) > 0)
+        );
+
+        CREATE TABLE address (
+            address_id INTEGER PRIMARY KEY,
+            house_num TEXT,  -- Optional
+            street_name TEXT, -- Optional
+            locality_name TEXT, -- Optional
+            city_name TEXT NOT NULL CHECK (LENGTH(city_name) > 0),
+            district_name TEXT NOT NULL CHECK (LENGTH(district_name) > 0),
+            pin_code TEXT NOT NULL CHECK (LENGTH(pin_code) > 0),
+            state TEXT NOT NULL CHECK (LENGTH(state) > 0),
+            country TEXT NOT NULL CHECK (LENGTH(country) > 0)
+        );
+
+        CREATE TABLE dealer (
+            dealer_id INTEGER PRIMARY KEY,
+            first_name TEXT NOT NULL CHECK (LENGTH(first_name) > 0),
+            middle_name TEXT,
+            last_name TEXT NOT NULL CHECK (LENGTH(last_name) > 0)
+        );
+
+        CREATE TABLE dealer_location (
+            dealer_id INT NOT NULL,
+            address_id INT NOT NULL,
+            description TEXT,
+            PRIMARY KEY (dealer_id, address_id),
+            FOREIGN KEY (dealer_id) REFERENCES dealer(dealer_id) ON DELETE CASCADE,
+            FOREIGN KEY (address_id) REFERENCES address(address_id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE dealer_contact (
+            dealer_id INT NOT NULL,
+            phone_id INT NOT NULL,
+            description TEXT,
+            PRIMARY KEY (dealer_id, phone_id),
+            FOREIGN KEY (dealer_id) REFERENCES dealer(dealer_id) ON DELETE CASCADE,
+            FOREIGN KEY (phone_id) REFERENCES phone(phone_id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE dealer_price (
+            product_id INT NOT NULL,
+            dealer_id INT NOT NULL,
+            price NUMERIC NOT NULL CHECK (price >= 0),
+            time_stamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (product_id, dealer_id, time_stamp),
+            FOREIGN KEY (product_id) REFERENCES product(product_id) ON DELETE CASCADE,
+            FOREIGN KEY (dealer_id) REFERENCES dealer(dealer_id) ON DELETE CASCADE
+        );
+        ",
+    )
+}
+
+fn primary_dealer_contacts(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        ALTER TABLE dealer_contact
+            ADD COLUMN is_primary INTEGER NOT NULL DEFAULT 0 CHECK (is_primary IN (0, 1));
+
+        UPDATE dealer_contact SET is_primary = 1
+        WHERE phone_id = (
+            SELECT MIN(dc.phone_id) FROM dealer_contact dc WHERE dc.dealer_id = dealer_contact.dealer_id
+        );
+
+        CREATE UNIQUE INDEX dealer_contact_primary ON dealer_contact(dealer_id) WHERE is_primary = 1;
+        ",
+    )
+}
+
+/// Rewrites every stored number that parses into E.164 form. Numbers that do
+/// not parse are left alone; `Store::get_unparsed_contacts` lists them.
+fn normalize_phone_numbers(transaction: &Transaction) -> rusqlite::Result<()> {
+    let phones = transaction
+        .prepare("SELECT phone_id, country_code, phone_number FROM phone")?
+        .query_map((), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut update = transaction
+        .prepare("UPDATE phone SET country_code = ?2, phone_number = ?3 WHERE phone_id = ?1")?;
+    for (phone_id, country_code, phone_number) in phones {
+        if let Some((country_code, phone_number)) = e164_parts(&country_code, &phone_number) {
+            update.execute(params![phone_id, country_code, phone_number])?;
+        }
+    }
+    Ok(())
+}
+
+/// The country code and national number of a stored number, read the way
+/// numbers were when `normalize_phone_numbers` shipped: a "+" prefix wins,
+/// a blank country code means India, and the number must be valid.
+fn e164_parts(country_code: &str, phone_number: &str) -> Option<(String, String)> {
+    let phone_number = phone_number.trim();
+    if phone_number.is_empty() {
+        return None;
+    }
+
+    let number = if phone_number.starts_with('+') {
+        phonenumber::parse(None, phone_number).ok()?
+    } else {
+        let code: u16 = match country_code.trim() {
+            "" => 91,
+            country_code => country_code.trim_start_matches('+').parse().ok()?,
+        };
+        let region = DATABASE
+            .region(&code)?
+            .first()?
+            .parse::<country::Id>()
+            .ok()?;
+        phonenumber::parse(Some(region), phone_number)
+            .ok()
+            .filter(|number: &PhoneNumber| number.code().value() == code)?
+    };
+    if !number.is_valid() {
+        return None;
+    }
+
+    let country_code = format!("+{}", number.code().value());
+    let e164 = number.format().mode(Mode::E164).to_string();
+    let national_number = e164[country_code.len()..].to_string();
+    Some((country_code, national_number))
+}
+
+/// `dealer_price.price` held rupees with NUMERIC affinity. It becomes
+/// `price_paise`, an exact INTEGER, by rebuilding the table.
+fn prices_in_paise(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE dealer_price_paise (
+            product_id INT NOT NULL,
+            dealer_id INT NOT NULL,
+            price_paise INTEGER NOT NULL CHECK (price_paise >= 0),
+            time_stamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (product_id, dealer_id, time_stamp),
+            FOREIGN KEY (product_id) REFERENCES product(product_id) ON DELETE CASCADE,
+            FOREIGN KEY (dealer_id) REFERENCES dealer(dealer_id) ON DELETE CASCADE
+        );
+
+        INSERT INTO dealer_price_paise (product_id, dealer_id, price_paise, time_stamp)
+        SELECT product_id, dealer_id, CAST(ROUND(price * 100) AS INTEGER), time_stamp
+        FROM dealer_price;
+
+        DROP TABLE dealer_price;
+        ALTER TABLE dealer_price_paise RENAME TO dealer_price;
+        ",
+    )
+}
+
+fn recent_products_and_settings(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE recent_product (
+            context TEXT NOT NULL CHECK (context IN ('comparison', 'data_feed')),
+            product_id INT NOT NULL,
+            recency INTEGER NOT NULL, -- Higher is more recent
+            PRIMARY KEY (context, product_id),
+            FOREIGN KEY (product_id) REFERENCES product(product_id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE setting (
+            name TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        ",
+    )
+}
+
+/// Dealers who have quoted a price for a product are taken to stock it.
+fn dealer_product_links(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE dealer_product (
+            dealer_id INT NOT NULL,
+            product_id INT NOT NULL,
+            PRIMARY KEY (dealer_id, product_id),
+            FOREIGN KEY (dealer_id) REFERENCES dealer(dealer_id) ON DELETE CASCADE,
+            FOREIGN KEY (product_id) REFERENCES product(product_id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX dealer_product_by_product ON dealer_product(product_id);
+
+        INSERT INTO dealer_product (dealer_id, product_id)
+        SELECT DISTINCT dealer_id, product_id FROM dealer_price;
+        ",
+    )
+}
+
+/// Archived rows keep their history but drop out of searches and the data feed.
+fn archived_products_and_dealers(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        ALTER TABLE product ADD COLUMN archived_at DATETIME DEFAULT NULL;
+        ALTER TABLE dealer ADD COLUMN archived_at DATETIME DEFAULT NULL;
+        ",
+    )
+}
+
+/// A trigram index over each product's name, brand, item and pack, keyed by
+/// `product_id` and kept current by triggers, so that searching does not
+/// have to read every product.
+fn product_search_index(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE VIRTUAL TABLE product_search USING fts5(
+            product_name, brand_name, item_name, pack_name,
+            tokenize = 'trigram'
+        );
+
+        INSERT INTO product_search (rowid, product_name, brand_name, item_name, pack_name)
+        SELECT p.product_id, p.name, b.name, i.name, p.pack_name
+        FROM product p
+        JOIN brand b ON b.brand_id = p.brand_id
+        JOIN item i ON i.item_id = p.item_id;
+
+        CREATE TRIGGER product_search_insert AFTER INSERT ON product BEGIN
+            INSERT INTO product_search (rowid, product_name, brand_name, item_name, pack_name)
+            SELECT NEW.product_id, NEW.name,
+                (SELECT name FROM brand WHERE brand_id = NEW.brand_id),
+                (SELECT name FROM item WHERE item_id = NEW.item_id),
+                NEW.pack_name;
+        END;
+
+        CREATE TRIGGER product_search_update AFTER UPDATE OF name, pack_name, brand_id, item_id ON product BEGIN
+            DELETE FROM product_search WHERE rowid = OLD.product_id;
+            INSERT INTO product_search (rowid, product_name, brand_name, item_name, pack_name)
+            SELECT NEW.product_id, NEW.name,
+                (SELECT name FROM brand WHERE brand_id = NEW.brand_id),
+                (SELECT name FROM item WHERE item_id = NEW.item_id),
+                NEW.pack_name;
+        END;
+
+        CREATE TRIGGER product_search_delete AFTER DELETE ON product BEGIN
+            DELETE FROM product_search WHERE rowid = OLD.product_id;
+        END;
+
+        CREATE TRIGGER product_search_brand AFTER UPDATE OF name ON brand BEGIN
+            UPDATE product_search SET brand_name = NEW.name
+            WHERE rowid IN (SELECT product_id FROM product WHERE brand_id = NEW.brand_id);
+        END;
+
+        CREATE TRIGGER product_search_item AFTER UPDATE OF name ON item BEGIN
+            UPDATE product_search SET item_name = NEW.name
+            WHERE rowid IN (SELECT product_id FROM product WHERE item_id = NEW.item_id);
+        END;
+        ",
+    )
+}
+
+/// Alternate names for a product, a brand or an item, such as "chini" for
+/// sugar. They are searched along with the real names, so the search index
+/// is rebuilt with a column for them; FTS5 tables cannot gain columns.
+fn aliases(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE alias (
+            alias_id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL CHECK (LENGTH(name) > 0),
+            product_id INT,
+            brand_id INT,
+            item_id INT,
+            CHECK ((product_id IS NOT NULL) + (brand_id IS NOT NULL) + (item_id IS NOT NULL) = 1),
+            FOREIGN KEY (product_id) REFERENCES product(product_id) ON DELETE CASCADE,
+            FOREIGN KEY (brand_id) REFERENCES brand(brand_id) ON DELETE CASCADE,
+            FOREIGN KEY (item_id) REFERENCES item(item_id) ON DELETE CASCADE
+        );
+
+        CREATE UNIQUE INDEX alias_by_target ON alias(
+            IFNULL(product_id, 0), IFNULL(brand_id, 0), IFNULL(item_id, 0), name COLLATE NOCASE
+        );
+
+        DROP TRIGGER product_search_insert;
+        DROP TRIGGER product_search_update;
+        DROP TRIGGER product_search_delete;
+        DROP TRIGGER product_search_brand;
+        DROP TRIGGER product_search_item;
+        DROP TABLE product_search;
+        ",
+    )?;
+
+    // Every alias of product `id`, whether given to it, its brand or its item.
+    let aliases_of = |id: &str| {
+        format!(
+            "(SELECT group_concat(alias.name, ' ') FROM alias, product
+              WHERE product.product_id = {id}
+              AND (alias.product_id = product.product_id
+                   OR alias.brand_id = product.brand_id
+                   OR alias.item_id = product.item_id))"
+        )
+    };
+    let reindex = |alias: &str| {
+        format!(
+            "UPDATE product_search SET aliases = {}
+             WHERE rowid IN (SELECT product_id FROM product
+                             WHERE product_id = {alias}.product_id
+                             OR brand_id = {alias}.brand_id
+                             OR item_id = {alias}.item_id);",
+            aliases_of("product_search.rowid")
+        )
+    };
+    let insert_new = format!(
+        "INSERT INTO product_search (rowid, product_name, brand_name, item_name, pack_name, aliases)
+         SELECT NEW.product_id, NEW.name,
+             (SELECT name FROM brand WHERE brand_id = NEW.brand_id),
+             (SELECT name FROM item WHERE item_id = NEW.item_id),
+             NEW.pack_name, {};",
+        aliases_of("NEW.product_id")
+    );
+
+    transaction.execute_batch(&format!(
+        "
+        CREATE VIRTUAL TABLE product_search USING fts5(
+            product_name, brand_name, item_name, pack_name, aliases,
+            tokenize = 'trigram'
+        );
+
+        INSERT INTO product_search (rowid, product_name, brand_name, item_name, pack_name)
+        SELECT p.product_id, p.name, b.name, i.name, p.pack_name
+        FROM product p
+        JOIN brand b ON b.brand_id = p.brand_id
+        JOIN item i ON i.item_id = p.item_id;
+
+        CREATE TRIGGER product_search_insert AFTER INSERT ON product BEGIN
+            {insert_new}
+        END;
+
+        CREATE TRIGGER product_search_update AFTER UPDATE OF name, pack_name, brand_id, item_id ON product BEGIN
+            DELETE FROM product_search WHERE rowid = OLD.product_id;
+            {insert_new}
+        END;
+
+        CREATE TRIGGER product_search_delete AFTER DELETE ON product BEGIN
+            DELETE FROM product_search WHERE rowid = OLD.product_id;
+        END;
+
+        CREATE TRIGGER product_search_brand AFTER UPDATE OF name ON brand BEGIN
+            UPDATE product_search SET brand_name = NEW.name
+            WHERE rowid IN (SELECT product_id FROM product WHERE brand_id = NEW.brand_id);
+        END;
+
+        CREATE TRIGGER product_search_item AFTER UPDATE OF name ON item BEGIN
+            UPDATE product_search SET item_name = NEW.name
+            WHERE rowid IN (SELECT product_id FROM product WHERE item_id = NEW.item_id);
+        END;
+
+        CREATE TRIGGER product_search_alias_insert AFTER INSERT ON alias BEGIN
+            {new}
+        END;
+
+        CREATE TRIGGER product_search_alias_update AFTER UPDATE ON alias BEGIN
+            {old}
+            {new}
+        END;
+
+        CREATE TRIGGER product_search_alias_delete AFTER DELETE ON alias BEGIN
+            {old}
+        END;
+        ",
+        new = reindex("NEW"),
+        old = reindex("OLD"),
+    ))
+}
+
+/// How much each product has been picked from the search results, decayed
+/// by age. The products in the recent lists start with one pick each.
+fn selection_boosts(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        CREATE TABLE product_selection (
+            product_id INT PRIMARY KEY,
+            weight REAL NOT NULL, -- Picks, each decayed to what it was worth at selected_at
+            selected_at DATETIME NOT NULL,
+            FOREIGN KEY (product_id) REFERENCES product(product_id) ON DELETE CASCADE
+        );
+
+        INSERT INTO product_selection (product_id, weight, selected_at)
+        SELECT DISTINCT product_id, 1.0, datetime('now') FROM recent_product;
+        ",
+    )
+}
+
+/// Each product's pack name read as `Pack`s are: `pack_count` packs of
+/// `pack_quantity` `pack_unit` each. Names that are not sizes leave all three
+/// NULL; `Store::get_unparsed_packs` lists them.
+fn structured_packs(transaction: &Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch(
+        "
+        ALTER TABLE product ADD COLUMN pack_count INTEGER;
+        ALTER TABLE product ADD COLUMN pack_quantity REAL;
+        ALTER TABLE product ADD COLUMN pack_unit TEXT;
+        ",
+    )?;
+
+    let packs = transaction
+        .prepare("SELECT product_id, pack_name FROM product")?
+        .query_map((), |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut update = transaction.prepare(
+        "UPDATE product SET pack_count = ?2, pack_quantity = ?3, pack_unit = ?4 WHERE product_id = ?1",
+    )?;
+    for (product_id, pack_name) in packs {
+        let (count, quantity, unit) = match pack_size(&pack_name) {
+            Some((count, quantity, unit)) => (Some(count), Some(quantity), Some(unit)),
+            None => (None, None, None),
+        };
+        update.execute(params![product_id, count, quantity, unit])?;
+    }
+    Ok(())
+}
+
+/// Each unit symbol stored by `structured_packs` and the lowercase spellings
+/// read as it.
+const PACK_UNITS: &[(&str, &[&str])] = &[
+    ("mg", &["mg", "milligram", "milligrams"]),
+    (
+        "g",
+        &["g", "gm", "gms", "gr", "grm", "gram", "grams", "gramme"],
+    ),
+    (
+        "kg",
+        &["kg", "kgs", "kilo", "kilos", "kilogram", "kilograms"],
+    ),
+    (
+        "ml",
+        &[
+            "ml",
+            "mls",
+            "millilitre",
+            "millilitres",
+            "milliliter",
+            "milliliters",
+        ],
+    ),
+    (
+        "L",
+        &[
+            "l", "ltr", "ltrs", "lt", "litre", "litres", "liter", "liters",
+        ],
+    ),
+    (
+        "pc",
+        &["pc", "pcs", "piece", "pieces", "nos", "no", "unit", "units"],
+    ),
+    ("dozen", &["dozen", "dozens", "doz", "dz"]),
+];
+
+/// A pack name read as `(count, quantity, unit symbol)` the way sizes were
+/// read when `structured_packs` shipped, e.g. "12x200ml" is 12 of 200 ml.
+fn pack_size(pack_name: &str) -> Option<(u32, f64, &'static str)> {
+    let text: String = pack_name
+        .to_lowercase()
+        .replace(['×', '*'], "x")
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ',')
+        .collect();
+
+    let is_count = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_digit());
+    let parts: Vec<&str> = text.split('x').collect();
+    let (count, size) = match parts[..] {
+        [size] => (1, size),
+        [count, size] | [size, count] if is_count(count) => (count.parse().ok()?, size),
+        _ => return None,
+    };
+    if count == 0 {
+        return None;
+    }
+
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (quantity, unit) = size.split_at(split);
+    let quantity = if quantity.is_empty() {
+        1.0
+    } else {
+        quantity.parse().ok()?
+    };
+    let spelling = unit.trim_end_matches('.');
+    let (unit, _) = PACK_UNITS
+        .iter()
+        .find(|(_, spellings)| spellings.contains(&spelling))?;
+    if quantity <= 0.0 {
+        return None;
+    }
+    Some((count, quantity, unit))
+}
+
+/// Brand and item names become unique regardless of case and spacing, which
+/// is how they are looked up from now on. Rows whose names only differ that
+/// way are merged into the oldest, and every name is tidied.
+fn case_insensitive_names(transaction: &Transaction) -> rusqlite::Result<()> {
+    for table in ["brand", "item"] {
+        let rows = transaction
+            .prepare(&format!(
+                "SELECT {table}_id, name FROM {table} ORDER BY {table}_id"
+            ))?
+            .query_map((), |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut kept: HashMap<String, i64> = HashMap::new();
+        let mut renames = Vec::new();
+        for (id, name) in rows {
+            let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+            match kept.entry(name.to_lowercase()) {
+                Entry::Occupied(entry) => merge_name_twin(transaction, table, *entry.get(), id)?,
+                Entry::Vacant(entry) => {
+                    entry.insert(id);
+                    renames.push((id, name));
+                }
+            }
+        }
+
+        let mut rename = transaction.prepare(&format!(
+            "UPDATE {table} SET name = ?2 WHERE {table}_id = ?1 AND name <> ?2"
+        ))?;
+        for (id, name) in renames {
+            rename.execute(params![id, name])?;
+        }
+
+        transaction.execute_batch(&format!(
+            "CREATE UNIQUE INDEX {table}_by_name ON {table}(name COLLATE NOCASE);"
+        ))?;
+    }
+    Ok(())
+}
+
+/// Points the `table` ("brand" or "item") row `duplicate_id`'s products and
+/// aliases at `keep_id` and deletes it, for `case_insensitive_names`. A kept
+/// item takes the duplicate's category if it has none.
+fn merge_name_twin(
+    transaction: &Transaction,
+    table: &str,
+    keep_id: i64,
+    duplicate_id: i64,
+) -> rusqlite::Result<()> {
+    if table == "item" {
+        transaction.execute(
+            "UPDATE item
+             SET category_id = COALESCE(category_id, (SELECT category_id FROM item WHERE item_id = ?2))
+             WHERE item_id = ?1",
+            params![keep_id, duplicate_id],
+        )?;
+    }
+    transaction.execute_batch(&format!(
+        "
+        UPDATE product SET {table}_id = {keep_id} WHERE {table}_id = {duplicate_id};
+        UPDATE OR IGNORE alias SET {table}_id = {keep_id} WHERE {table}_id = {duplicate_id};
+        DELETE FROM alias WHERE {table}_id = {duplicate_id};
+        DELETE FROM {table} WHERE {table}_id = {duplicate_id};
+        "
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(connection: &Connection) -> Vec<String> {
+        connection
+            .prepare("SELECT sql FROM sqlite_master WHERE sql IS NOT NULL ORDER BY name")
+            .unwrap()
+            .query_map((), |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    fn fresh_schema() -> Vec<String> {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        schema(&connection)
+    }
+
+    #[test]
+    fn new_database_is_at_current_version() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), CURRENT_VERSION);
+    }
+
+    #[test]
+    fn every_historic_version_upgrades_to_current() {
+        let expected = fresh_schema();
+
+        for version in 0..=CURRENT_VERSION {
+            let mut connection = Connection::open_in_memory().unwrap();
+            migrate_to(&mut connection, version).unwrap();
+            assert_eq!(schema_version(&connection).unwrap(), version);
+
+            migrate(&mut connection).unwrap();
+            assert_eq!(schema_version(&connection).unwrap(), CURRENT_VERSION);
+            assert_eq!(schema(&connection), expected, "upgrade from v{version}");
+        }
+    }
+
+    #[test]
+    fn unversioned_legacy_database_is_adopted() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        {
+            let transaction = connection.transaction().unwrap();
+            initial_schema(&transaction).unwrap();
+            transaction.commit().unwrap();
+        }
+        connection
+            .execute(
+                "INSERT INTO dealer (first_name, last_name) VALUES ('Ramesh', 'Gupta')",
+                (),
+            )
+            .unwrap();
+        assert_eq!(schema_version(&connection).unwrap(), 0);
+
+        migrate(&mut connection).unwrap();
+
+        assert_eq!(schema_version(&connection).unwrap(), CURRENT_VERSION);
+        assert_eq!(schema(&connection), fresh_schema());
+        let dealers: i64 = connection
+            .query_row("SELECT COUNT(*) FROM dealer", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(dealers, 1);
+    }
+
+    #[test]
+    fn newer_database_is_refused() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "user_version", CURRENT_VERSION + 1)
+            .unwrap();
+
+        match migrate(&mut connection) {
+            Err(StoreError::SchemaTooNew { found, supported }) => {
+                assert_eq!(found, CURRENT_VERSION + 1);
+                assert_eq!(supported, CURRENT_VERSION);
+            }
+            other => panic!("expected SchemaTooNew, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn oldest_contact_becomes_primary() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate_to(&mut connection, 1).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO dealer (dealer_id, first_name, last_name) VALUES (1, 'Ramesh', 'Gupta');
+                 INSERT INTO phone (phone_id, country_code, phone_number) VALUES (7, '+91', '9876543210');
+                 INSERT INTO phone (phone_id, country_code, phone_number) VALUES (3, '+91', '9876543211');
+                 INSERT INTO dealer_contact (dealer_id, phone_id) VALUES (1, 7);
+                 INSERT INTO dealer_contact (dealer_id, phone_id) VALUES (1, 3);",
+            )
+            .unwrap();
+
+        migrate_to(&mut connection, 2).unwrap();
+
+        let primary: Vec<i64> = connection
+            .prepare("SELECT phone_id FROM dealer_contact WHERE is_primary")
+            .unwrap()
+            .query_map((), |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(primary, vec![3]);
+    }
+
+    #[test]
+    fn phone_numbers_are_normalized() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate_to(&mut connection, 2).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO phone (phone_id, country_code, phone_number) VALUES (1, '+91', '098765 43210');
+                 INSERT INTO phone (phone_id, country_code, phone_number) VALUES (2, '91', '+44 20 7946 0958');
+                 INSERT INTO phone (phone_id, country_code, phone_number) VALUES (3, '+91', 'call shop');",
+            )
+            .unwrap();
+
+        migrate_to(&mut connection, 3).unwrap();
+
+        let phones: Vec<(String, String)> = connection
+            .prepare("SELECT country_code, phone_number FROM phone ORDER BY phone_id")
+            .unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            phones,
+            vec![
+                ("+91".to_string(), "9876543210".to_string()),
+                ("+44".to_string(), "2079460958".to_string()),
+                ("+91".to_string(), "call shop".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn prices_are_converted_to_paise() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate_to(&mut connection, 3).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO dealer (dealer_id, first_name, last_name) VALUES (1, 'Ramesh', 'Gupta');
+                 INSERT INTO brand (brand_id, name) VALUES (1, 'Amul');
+                 INSERT INTO item (item_id, name) VALUES (1, 'Butter');
+                 INSERT INTO product (product_id, name, pack_name, item_id, brand_id) VALUES (1, 'Butter', '500g', 1, 1);
+                 INSERT INTO dealer_price (product_id, dealer_id, price, time_stamp) VALUES (1, 1, 250, '2024-01-01 10:00:00');
+                 INSERT INTO dealer_price (product_id, dealer_id, price, time_stamp) VALUES (1, 1, 42.5, '2024-02-01 10:00:00');",
+            )
+            .unwrap();
+
+        migrate_to(&mut connection, 4).unwrap();
+
+        let prices: Vec<i64> = connection
+            .prepare("SELECT price_paise FROM dealer_price ORDER BY time_stamp")
+            .unwrap()
+            .query_map((), |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(prices, vec![25000, 4250]);
+    }
+
+    #[test]
+    fn priced_pairs_become_links() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate_to(&mut connection, 5).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO dealer (dealer_id, first_name, last_name) VALUES (1, 'Ramesh', 'Gupta');
+                 INSERT INTO dealer (dealer_id, first_name, last_name) VALUES (2, 'Suresh', 'Jain');
+                 INSERT INTO brand (brand_id, name) VALUES (1, 'Amul');
+                 INSERT INTO item (item_id, name) VALUES (1, 'Butter');
+                 INSERT INTO product (product_id, name, pack_name, item_id, brand_id) VALUES (1, 'Butter', '500g', 1, 1);
+                 INSERT INTO dealer_price (product_id, dealer_id, price_paise, time_stamp) VALUES (1, 1, 25000, '2024-01-01 10:00:00');
+                 INSERT INTO dealer_price (product_id, dealer_id, price_paise, time_stamp) VALUES (1, 1, 26000, '2024-02-01 10:00:00');",
+            )
+            .unwrap();
+
+        migrate_to(&mut connection, 6).unwrap();
+
+        let links: Vec<(i64, i64)> = connection
+            .prepare("SELECT dealer_id, product_id FROM dealer_product")
+            .unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(links, vec![(1, 1)]);
+    }
+
+    #[test]
+    fn existing_products_are_indexed() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate_to(&mut connection, 7).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO brand (brand_id, name) VALUES (1, 'Amul');
+                 INSERT INTO item (item_id, name) VALUES (1, 'Butter');
+                 INSERT INTO product (product_id, name, pack_name, item_id, brand_id) VALUES (7, 'Salted Butter', '500g', 1, 1);",
+            )
+            .unwrap();
+
+        migrate_to(&mut connection, 8).unwrap();
+
+        let found: i64 = connection
+            .query_row(
+                "SELECT rowid FROM product_search WHERE product_search MATCH 'amu'",
+                (),
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(found, 7);
+
+        // Rebuilding the index for aliases keeps what was there.
+        migrate_to(&mut connection, 9).unwrap();
+        let found: i64 = connection
+            .query_row(
+                "SELECT rowid FROM product_search WHERE product_search MATCH 'tte'",
+                (),
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(found, 7);
+    }
+
+    #[test]
+    fn pack_names_are_structured() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate_to(&mut connection, 10).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO brand (brand_id, name) VALUES (1, 'Amul');
+                 INSERT INTO item (item_id, name) VALUES (1, 'Milk');
+                 INSERT INTO product (product_id, name, pack_name, item_id, brand_id) VALUES (1, 'Taaza', '12 x 200ML', 1, 1);
+                 INSERT INTO product (product_id, name, pack_name, item_id, brand_id) VALUES (2, 'Gold', 'Family Pack', 1, 1);",
+            )
+            .unwrap();
+
+        migrate_to(&mut connection, 11).unwrap();
+
+        let packs: Vec<(Option<u32>, Option<f64>, Option<String>)> = connection
+            .prepare("SELECT pack_count, pack_quantity, pack_unit FROM product ORDER BY product_id")
+            .unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            packs,
+            vec![
+                (Some(12), Some(200.0), Some("ml".to_string())),
+                (None, None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_differing_in_case_are_merged() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate_to(&mut connection, 11).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO brand (brand_id, name) VALUES (1, 'Amul');
+                 INSERT INTO brand (brand_id, name) VALUES (2, 'amul ');
+                 INSERT INTO brand (brand_id, name) VALUES (3, 'Mother  Dairy');
+                 INSERT INTO item (item_id, name) VALUES (1, 'Butter');
+                 INSERT INTO item (item_id, name, category_id) VALUES (2, 'BUTTER', NULL);
+                 INSERT INTO alias (name, brand_id) VALUES ('Amul Dairy', 2);
+                 INSERT INTO product (product_id, name, pack_name, item_id, brand_id) VALUES (1, 'Butter', '500g', 1, 1);
+                 INSERT INTO product (product_id, name, pack_name, item_id, brand_id) VALUES (2, 'Butter', '100g', 2, 2);
+                 INSERT INTO product (product_id, name, pack_name, item_id, brand_id) VALUES (3, 'Butter', '100g', 2, 3);",
+            )
+            .unwrap();
+
+        migrate_to(&mut connection, 12).unwrap();
+
+        let names = |table: &str| -> Vec<(i64, String)> {
+            connection
+                .prepare(&format!(
+                    "SELECT {table}_id, name FROM {table} ORDER BY {table}_id"
+                ))
+                .unwrap()
+                .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap()
+        };
+        assert_eq!(
+            names("brand"),
+            vec![(1, "Amul".to_string()), (3, "Mother Dairy".to_string())]
+        );
+        assert_eq!(names("item"), vec![(1, "Butter".to_string())]);
+        let products: Vec<(i64, i64)> = connection
+            .prepare("SELECT brand_id, item_id FROM product ORDER BY product_id")
+            .unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(products, vec![(1, 1), (1, 1), (3, 1)]);
+        let alias_brand: i64 = connection
+            .query_row("SELECT brand_id FROM alias", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(alias_brand, 1);
+        assert!(connection
+            .execute("INSERT INTO brand (name) VALUES ('AMUL')", ())
+            .is_err());
+    }
+}
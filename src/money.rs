@@ -0,0 +1,142 @@
+use crate::{Result, StoreError};
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use std::fmt;
+use std::str::FromStr;
+
+/// An amount in rupees, held as a whole number of paise so that ₹42.50 is
+/// stored and compared exactly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_paise(paise: i64) -> Self {
+        Money(paise)
+    }
+
+    pub const fn from_rupees(rupees: i64) -> Self {
+        Money(rupees * 100)
+    }
+
+    pub const fn paise(self) -> i64 {
+        self.0
+    }
+}
+
+impl FromStr for Money {
+    type Err = StoreError;
+
+    /// Reads amounts the way they get typed: "42", "42.5", "₹1,23,456.00",
+    /// "Rs. 40" or "40/-". At most two decimal places are allowed.
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid =
+            || StoreError::validation(format!("\"{}\" is not a valid price", text.trim()));
+
+        let mut amount = text.trim();
+        for prefix in ["₹", "Rs.", "Rs", "INR"] {
+            let head = amount.get(..prefix.len());
+            if head.is_some_and(|head| head.eq_ignore_ascii_case(prefix)) {
+                amount = amount[prefix.len()..].trim_start();
+                break;
+            }
+        }
+        let amount = amount.strip_suffix("/-").unwrap_or(amount).replace(',', "");
+
+        let (rupees, paise) = amount.split_once('.').unwrap_or((&amount, ""));
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if rupees.is_empty() || !is_digits(rupees) || !is_digits(paise) || paise.len() > 2 {
+            return Err(invalid());
+        }
+
+        let rupees: i64 = rupees.parse().map_err(|_| invalid())?;
+        let paise: i64 = format!("{paise:0<2}").parse().map_err(|_| invalid())?;
+        rupees
+            .checked_mul(100)
+            .and_then(|total| total.checked_add(paise))
+            .map(Money)
+            .ok_or_else(invalid)
+    }
+}
+
+/// Formats as "₹1,23,456.00", grouping digits the Indian way: the last
+/// three together, then pairs.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let paise = self.0.unsigned_abs();
+        let rupees = (paise / 100).to_string();
+
+        let (head, tail) = rupees.split_at(rupees.len().saturating_sub(3));
+        let mut groups: Vec<&str> = Vec::new();
+        let mut rest = head;
+        while rest.len() > 2 {
+            let (front, pair) = rest.split_at(rest.len() - 2);
+            groups.push(pair);
+            rest = front;
+        }
+        if !rest.is_empty() {
+            groups.push(rest);
+        }
+        groups.reverse();
+        groups.push(tail);
+
+        write!(f, "{sign}₹{}.{:02}", groups.join(","), paise % 100)
+    }
+}
+
+impl ToSql for Money {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        self.0.to_sql()
+    }
+}
+
+impl FromSql for Money {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        i64::column_result(value).map(Money)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prices_are_parsed_exactly() {
+        assert_eq!("42".parse::<Money>().unwrap(), Money::from_paise(4200));
+        assert_eq!("42.5".parse::<Money>().unwrap(), Money::from_paise(4250));
+        assert_eq!(
+            " ₹42.05 ".parse::<Money>().unwrap(),
+            Money::from_paise(4205)
+        );
+        assert_eq!(
+            "₹1,23,456.00".parse::<Money>().unwrap(),
+            Money::from_rupees(123456)
+        );
+        assert_eq!("Rs. 40/-".parse::<Money>().unwrap(), Money::from_rupees(40));
+
+        for invalid in [
+            "",
+            "₹",
+            "€5",
+            "42.505",
+            "-5",
+            "4x",
+            ".5",
+            "99999999999999999999",
+        ] {
+            let result = invalid.parse::<Money>();
+            assert!(result.is_err(), "{invalid:?} parsed as {result:?}");
+        }
+    }
+
+    #[test]
+    fn prices_are_grouped_the_indian_way() {
+        assert_eq!(Money::from_paise(5).to_string(), "₹0.05");
+        assert_eq!(Money::from_paise(4250).to_string(), "₹42.50");
+        assert_eq!(Money::from_rupees(1000).to_string(), "₹1,000.00");
+        assert_eq!(Money::from_rupees(123456).to_string(), "₹1,23,456.00");
+        assert_eq!(Money::from_rupees(12345678).to_string(), "₹1,23,45,678.00");
+        assert_eq!(Money::from_paise(-150).to_string(), "-₹1.50");
+    }
+}
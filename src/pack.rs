@@ -0,0 +1,357 @@
+use crate::{Money, Product, ProductFilter, ProductSort, Result, Store};
+use std::fmt;
+
+/// What a unit measures. Packs are only comparable within one measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Measure {
+    /// In grams.
+    Mass,
+    /// In millilitres.
+    Volume,
+    /// In pieces.
+    Count,
+}
+
+/// A unit a pack size can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Milligram,
+    Gram,
+    Kilogram,
+    Millilitre,
+    Litre,
+    Piece,
+    Dozen,
+}
+
+struct UnitInfo {
+    unit: Unit,
+    /// How the unit is stored and shown.
+    symbol: &'static str,
+    /// Lowercase, without spaces or a trailing full stop.
+    spellings: &'static [&'static str],
+    measure: Measure,
+    /// How many grams, millilitres or pieces one of the unit is.
+    base_amount: f64,
+}
+
+/// The unit conversion table.
+const UNITS: &[UnitInfo] = &[
+    UnitInfo {
+        unit: Unit::Milligram,
+        symbol: "mg",
+        spellings: &["mg", "milligram", "milligrams"],
+        measure: Measure::Mass,
+        base_amount: 0.001,
+    },
+    UnitInfo {
+        unit: Unit::Gram,
+        symbol: "g",
+        spellings: &["g", "gm", "gms", "gr", "grm", "gram", "grams", "gramme"],
+        measure: Measure::Mass,
+        base_amount: 1.0,
+    },
+    UnitInfo {
+        unit: Unit::Kilogram,
+        symbol: "kg",
+        spellings: &["kg", "kgs", "kilo", "kilos", "kilogram", "kilograms"],
+        measure: Measure::Mass,
+        base_amount: 1000.0,
+    },
+    UnitInfo {
+        unit: Unit::Millilitre,
+        symbol: "ml",
+        spellings: &[
+            "ml",
+            "mls",
+            "millilitre",
+            "millilitres",
+            "milliliter",
+            "milliliters",
+        ],
+        measure: Measure::Volume,
+        base_amount: 1.0,
+    },
+    UnitInfo {
+        unit: Unit::Litre,
+        symbol: "L",
+        spellings: &[
+            "l", "ltr", "ltrs", "lt", "litre", "litres", "liter", "liters",
+        ],
+        measure: Measure::Volume,
+        base_amount: 1000.0,
+    },
+    UnitInfo {
+        unit: Unit::Piece,
+        symbol: "pc",
+        spellings: &["pc", "pcs", "piece", "pieces", "nos", "no", "unit", "units"],
+        measure: Measure::Count,
+        base_amount: 1.0,
+    },
+    UnitInfo {
+        unit: Unit::Dozen,
+        symbol: "dozen",
+        spellings: &["dozen", "dozens", "doz", "dz"],
+        measure: Measure::Count,
+        base_amount: 12.0,
+    },
+];
+
+impl Unit {
+    fn info(self) -> &'static UnitInfo {
+        UNITS
+            .iter()
+            .find(|info| info.unit == self)
+            .expect("every unit is in the table")
+    }
+
+    pub fn symbol(self) -> &'static str {
+        self.info().symbol
+    }
+
+    pub fn measure(self) -> Measure {
+        self.info().measure
+    }
+
+    /// The unit stored as `symbol`.
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        UNITS
+            .iter()
+            .find(|info| info.symbol == symbol)
+            .map(|info| info.unit)
+    }
+
+    fn from_spelling(spelling: &str) -> Option<Self> {
+        UNITS
+            .iter()
+            .find(|info| info.spellings.contains(&spelling))
+            .map(|info| info.unit)
+    }
+}
+
+/// A pack size read from its free-text name: `count` packs of `quantity`
+/// `unit` each, so "12x200ml" is 12 of 200 ml.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pack {
+    pub count: u32,
+    pub quantity: f64,
+    pub unit: Unit,
+}
+
+impl Pack {
+    /// Reads the ways pack sizes get typed, such as "500g", "1 Kg", "1.5L",
+    /// "12x200ml", "200 ml x 12", "6 pcs" or "1 dozen". Commas are taken as
+    /// thousands separators. `None` when the name is not a size.
+    pub fn parse(pack_name: &str) -> Option<Pack> {
+        let text: String = pack_name
+            .to_lowercase()
+            .replace(['×', '*'], "x")
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != ',')
+            .collect();
+
+        let parts: Vec<&str> = text.split('x').collect();
+        let (count, size) = match parts[..] {
+            [size] => (1, size),
+            [count, size] | [size, count] if is_count(count) => (count.parse().ok()?, size),
+            _ => return None,
+        };
+        if count == 0 {
+            return None;
+        }
+
+        let split = size
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(size.len());
+        let (quantity, unit) = size.split_at(split);
+        let quantity = if quantity.is_empty() {
+            1.0
+        } else {
+            quantity.parse().ok()?
+        };
+        let unit = Unit::from_spelling(unit.trim_end_matches('.'))?;
+        if quantity <= 0.0 {
+            return None;
+        }
+
+        Some(Pack {
+            count,
+            quantity,
+            unit,
+        })
+    }
+
+    pub fn measure(&self) -> Measure {
+        self.unit.measure()
+    }
+
+    /// The whole pack in grams, millilitres or pieces.
+    pub fn base_amount(&self) -> f64 {
+        self.count as f64 * self.quantity * self.unit.info().base_amount
+    }
+
+    /// What `price` for the whole pack comes to per kilogram, litre or piece.
+    pub fn unit_price(&self, price: Money) -> UnitPrice {
+        let (per, base_amount) = match self.measure() {
+            Measure::Mass => ("kg", 1000.0),
+            Measure::Volume => ("L", 1000.0),
+            Measure::Count => ("pc", 1.0),
+        };
+        let paise = price.paise() as f64 * base_amount / self.base_amount();
+        UnitPrice {
+            price: Money::from_paise(paise.round() as i64),
+            measure: self.measure(),
+            per,
+        }
+    }
+}
+
+/// "500g", or "12 x 200ml" for several.
+impl fmt::Display for Pack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count > 1 {
+            write!(f, "{} x ", self.count)?;
+        }
+        write!(f, "{}{}", self.quantity, self.unit.symbol())
+    }
+}
+
+/// A price per kilogram, litre or piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnitPrice {
+    pub price: Money,
+    pub measure: Measure,
+    /// The unit the price is per, e.g. "kg".
+    pub per: &'static str,
+}
+
+/// "₹240.00/kg"
+impl fmt::Display for UnitPrice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.price, self.per)
+    }
+}
+
+impl Store {
+    /// Active products whose pack name is not a size `Pack::parse` can read,
+    /// alphabetically, so that they can be renamed by hand.
+    pub fn get_unparsed_packs(&self) -> Result<Vec<Product>> {
+        self.search_products(&ProductFilter {
+            unparsed_packs: true,
+            sort: ProductSort::Name,
+            ..Default::default()
+        })
+    }
+}
+
+/// The `pack_count, pack_quantity, pack_unit` columns for a product packed
+/// as `pack_name`, all NULL when it is not a size.
+pub(crate) fn pack_columns(pack_name: &str) -> (Option<u32>, Option<f64>, Option<&'static str>) {
+    match Pack::parse(pack_name) {
+        Some(pack) => (
+            Some(pack.count),
+            Some(pack.quantity),
+            Some(pack.unit.symbol()),
+        ),
+        None => (None, None, None),
+    }
+}
+
+/// Reads a pack back from its columns.
+pub(crate) fn pack_from_columns(
+    count: Option<u32>,
+    quantity: Option<f64>,
+    unit: Option<String>,
+) -> Option<Pack> {
+    Some(Pack {
+        count: count?,
+        quantity: quantity?,
+        unit: Unit::from_symbol(&unit?)?,
+    })
+}
+
+fn is_count(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn pack_names_are_read() {
+        let pack = |count, quantity, unit| {
+            Some(Pack {
+                count,
+                quantity,
+                unit,
+            })
+        };
+        assert_eq!(Pack::parse("500g"), pack(1, 500.0, Unit::Gram));
+        assert_eq!(Pack::parse("1 Kg"), pack(1, 1.0, Unit::Kilogram));
+        assert_eq!(Pack::parse("1.5L"), pack(1, 1.5, Unit::Litre));
+        assert_eq!(Pack::parse("12x200ml"), pack(12, 200.0, Unit::Millilitre));
+        assert_eq!(
+            Pack::parse("200 ML × 12"),
+            pack(12, 200.0, Unit::Millilitre)
+        );
+        assert_eq!(Pack::parse("1,000 gms."), pack(1, 1000.0, Unit::Gram));
+        assert_eq!(Pack::parse("6 pcs"), pack(1, 6.0, Unit::Piece));
+        assert_eq!(Pack::parse("Dozen"), pack(1, 1.0, Unit::Dozen));
+
+        for unreadable in [
+            "",
+            "family pack",
+            "500",
+            "0g",
+            "0x1kg",
+            "2x3x1kg",
+            "1.2.3kg",
+        ] {
+            assert_eq!(Pack::parse(unreadable), None, "{unreadable}");
+        }
+    }
+
+    #[test]
+    fn unit_prices_compare_pack_sizes() {
+        let per_kg = |pack: &str, rupees| {
+            Pack::parse(pack)
+                .unwrap()
+                .unit_price(Money::from_rupees(rupees))
+        };
+        assert_eq!(per_kg("1kg", 240).to_string(), "₹240.00/kg");
+        assert_eq!(per_kg("500g", 130).to_string(), "₹260.00/kg");
+        assert!(per_kg("1kg", 240).price < per_kg("500g", 130).price);
+        assert_eq!(per_kg("12x200ml", 480).to_string(), "₹200.00/L");
+        assert_eq!(per_kg("1 dozen", 60).to_string(), "₹5.00/pc");
+        assert_eq!(Pack::parse("12X200ML").unwrap().to_string(), "12 x 200ml");
+    }
+
+    #[test]
+    fn products_keep_their_pack_sizes() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let ghee = store
+            .add_product("Pure Ghee", "Amul", "Ghee", "1 Ltr", None)
+            .unwrap();
+        let loose = store
+            .add_product("Loose Ghee", "Amul", "Ghee", "Tin", None)
+            .unwrap();
+
+        assert_eq!(store.get_product(ghee).unwrap().pack, Pack::parse("1L"));
+        assert_eq!(store.get_product(loose).unwrap().pack, None);
+        let unparsed: Vec<_> = store
+            .get_unparsed_packs()
+            .unwrap()
+            .iter()
+            .map(|product| product.product_id)
+            .collect();
+        assert_eq!(unparsed, vec![loose]);
+
+        store
+            .update_product(loose, "Loose Ghee", "Amul", "Ghee", "15 kg", None)
+            .unwrap();
+        assert_eq!(store.get_product(loose).unwrap().pack, Pack::parse("15kg"));
+        assert!(store.get_unparsed_packs().unwrap().is_empty());
+    }
+}
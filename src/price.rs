@@ -0,0 +1,298 @@
+use crate::{ensure_exists, Money, Result, Store, StoreError};
+use rusqlite::{params, Connection};
+
+/// One recorded price. `recorded_at` is UTC in SQLite's
+/// "YYYY-MM-DD HH:MM:SS" form, so timestamps compare as strings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricePoint {
+    pub dealer_id: i64,
+    pub price: Money,
+    pub recorded_at: String,
+}
+
+/// The most recent time a dealer's price actually moved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceChange {
+    pub from: Money,
+    pub to: Money,
+    pub changed_at: String,
+}
+
+/// How a dealer's price for a product behaved over a window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceSummary {
+    /// Prices recorded inside the window.
+    pub samples: usize,
+    pub min: Money,
+    pub max: Money,
+    /// Rounded to the nearest paisa.
+    pub mean: Money,
+    pub latest: Money,
+    /// Looks at the whole history, not just the window.
+    pub last_change: Option<PriceChange>,
+    /// From the price in effect when the window opened to `latest`. `None`
+    /// when that price was zero.
+    pub percent_change: Option<f64>,
+}
+
+impl Store {
+    /// Records a price as of `recorded_at`, which SQLite's `datetime()` must
+    /// understand, e.g. "2024-03-01 09:30:00" or "now". A second price for
+    /// the same moment replaces the first.
+    pub fn update_price_at(
+        &self,
+        product_id: i64,
+        dealer_id: i64,
+        price: Money,
+        recorded_at: &str,
+    ) -> Result<()> {
+        insert_price(&self.connection, product_id, dealer_id, price, recorded_at)
+    }
+
+    /// Every price the dealer has quoted for the product, oldest first.
+    pub fn get_price_history(&self, product_id: i64, dealer_id: i64) -> Result<Vec<PricePoint>> {
+        self.ensure_exists("product", product_id)?;
+        self.ensure_exists("dealer", dealer_id)?;
+        self.query_price_points(
+            "WHERE product_id = ?1 AND dealer_id = ?2",
+            params![product_id, dealer_id],
+        )
+    }
+
+    /// Every price any dealer has quoted for the product, oldest first.
+    pub fn get_product_price_history(&self, product_id: i64) -> Result<Vec<PricePoint>> {
+        self.ensure_exists("product", product_id)?;
+        self.query_price_points("WHERE product_id = ?1", params![product_id])
+    }
+
+    /// Summarises the dealer's prices for the product over the last `days`
+    /// days, or over all time for `None`. Returns `None` when nothing was
+    /// recorded in the window.
+    pub fn get_price_summary(
+        &self,
+        product_id: i64,
+        dealer_id: i64,
+        days: Option<u32>,
+    ) -> Result<Option<PriceSummary>> {
+        let history = self.get_price_history(product_id, dealer_id)?;
+
+        let window_start: Option<String> = match days {
+            Some(days) => Some(self.connection.query_row(
+                "SELECT datetime('now', ?1)",
+                params![format!("-{days} days")],
+                |row| row.get(0),
+            )?),
+            None => None,
+        };
+        let opened_at = window_start
+            .as_deref()
+            .map(|start| history.partition_point(|point| point.recorded_at.as_str() < start))
+            .unwrap_or(0);
+
+        let window = &history[opened_at..];
+        let Some(latest) = window.last() else {
+            return Ok(None);
+        };
+
+        let prices = window.iter().map(|point| point.price.paise());
+        let total: i64 = prices.clone().sum();
+        let count = window.len() as i64;
+        // Round half up so that e.g. ₹10.005 averages to ₹10.01.
+        let mean = Money::from_paise((2 * total + count) / (2 * count));
+
+        // The price in effect when the window opened is the last one quoted
+        // before it, if there was one.
+        let baseline = history[..opened_at]
+            .last()
+            .unwrap_or(&window[0])
+            .price
+            .paise();
+        let percent_change = (baseline != 0)
+            .then(|| (latest.price.paise() - baseline) as f64 * 100.0 / baseline as f64);
+
+        let last_change = history
+            .windows(2)
+            .rev()
+            .find(|pair| pair[0].price != pair[1].price)
+            .map(|pair| PriceChange {
+                from: pair[0].price,
+                to: pair[1].price,
+                changed_at: pair[1].recorded_at.clone(),
+            });
+
+        Ok(Some(PriceSummary {
+            samples: window.len(),
+            min: Money::from_paise(prices.clone().min().unwrap_or_default()),
+            max: Money::from_paise(prices.max().unwrap_or_default()),
+            mean,
+            latest: latest.price,
+            last_change,
+            percent_change,
+        }))
+    }
+
+    fn query_price_points(
+        &self,
+        filter: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<PricePoint>> {
+        let points = self
+            .connection
+            .prepare(&format!(
+                "SELECT dealer_id, price_paise, time_stamp FROM dealer_price {filter} ORDER BY time_stamp, dealer_id"
+            ))?
+            .query_map(params, |row| {
+                Ok(PricePoint {
+                    dealer_id: row.get(0)?,
+                    price: row.get(1)?,
+                    recorded_at: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(points)
+    }
+}
+
+/// What `update_price_at` does, inside the caller's transaction.
+pub(crate) fn insert_price(
+    connection: &Connection,
+    product_id: i64,
+    dealer_id: i64,
+    price: Money,
+    recorded_at: &str,
+) -> Result<()> {
+    ensure_exists(connection, "product", product_id)?;
+    ensure_exists(connection, "dealer", dealer_id)?;
+    if price < Money::ZERO {
+        return Err(StoreError::validation("A price cannot be negative"));
+    }
+
+    let timestamp: Option<String> =
+        connection.query_row("SELECT datetime(?1)", params![recorded_at], |row| {
+            row.get(0)
+        })?;
+    let timestamp = timestamp.ok_or_else(|| {
+        StoreError::validation(format!("\"{recorded_at}\" is not a valid date and time"))
+    })?;
+
+    connection.execute(
+        "
+        INSERT INTO dealer_price (product_id, dealer_id, price_paise, time_stamp)
+        VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT (product_id, dealer_id, time_stamp) DO UPDATE SET price_paise = excluded.price_paise",
+        params![product_id, dealer_id, price, timestamp],
+    )?;
+    // Quoting a price means the dealer stocks the product.
+    connection.execute(
+        "INSERT OR IGNORE INTO dealer_product (dealer_id, product_id) VALUES (?1, ?2)",
+        params![dealer_id, product_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn days_ago(store: &Store, days: u32) -> String {
+        store
+            .connection
+            .query_row(
+                "SELECT datetime('now', ?1)",
+                [format!("-{days} days")],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    fn store_with_product() -> (Store, i64, i64) {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let product_id = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let dealer_id = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+        (store, product_id, dealer_id)
+    }
+
+    #[test]
+    fn history_lists_every_price_in_order() {
+        let (mut store, product_id, ramesh) = store_with_product();
+        let suresh = store.add_dealer("Suresh", None, "Jain", &[]).unwrap();
+
+        for (dealer_id, rupees, at) in [
+            (ramesh, 250, "2024-03-01 10:00:00"),
+            (suresh, 245, "2024-02-01 10:00:00"),
+            (ramesh, 255, "2024-04-01 10:00:00"),
+        ] {
+            store
+                .update_price_at(product_id, dealer_id, Money::from_rupees(rupees), at)
+                .unwrap();
+        }
+        // A correction for the same moment replaces the earlier entry.
+        store
+            .update_price_at(
+                product_id,
+                ramesh,
+                Money::from_rupees(256),
+                "2024-04-01 10:00:00",
+            )
+            .unwrap();
+
+        let history = store.get_price_history(product_id, ramesh).unwrap();
+        let prices: Vec<_> = history.iter().map(|point| point.price).collect();
+        assert_eq!(
+            prices,
+            vec![Money::from_rupees(250), Money::from_rupees(256)]
+        );
+
+        let everyone = store.get_product_price_history(product_id).unwrap();
+        let dealers: Vec<_> = everyone.iter().map(|point| point.dealer_id).collect();
+        assert_eq!(dealers, vec![suresh, ramesh, ramesh]);
+
+        assert!(matches!(
+            store.update_price_at(product_id, ramesh, Money::from_rupees(1), "last tuesday"),
+            Err(StoreError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn summary_shows_prices_creeping_up() {
+        let (store, product_id, dealer_id) = store_with_product();
+        for (days, paise) in [(60, 20000), (20, 20000), (10, 21000), (5, 22000)] {
+            let at = days_ago(&store, days);
+            store
+                .update_price_at(product_id, dealer_id, Money::from_paise(paise), &at)
+                .unwrap();
+        }
+
+        let summary = store
+            .get_price_summary(product_id, dealer_id, Some(30))
+            .unwrap()
+            .unwrap();
+        assert_eq!(summary.samples, 3);
+        assert_eq!(summary.min, Money::from_rupees(200));
+        assert_eq!(summary.max, Money::from_rupees(220));
+        assert_eq!(summary.mean, Money::from_rupees(210));
+        assert_eq!(summary.latest, Money::from_rupees(220));
+        assert_eq!(summary.percent_change, Some(10.0));
+        let change = summary.last_change.unwrap();
+        assert_eq!(
+            (change.from, change.to),
+            (Money::from_rupees(210), Money::from_rupees(220))
+        );
+
+        assert_eq!(
+            store
+                .get_price_summary(product_id, dealer_id, Some(1))
+                .unwrap(),
+            None
+        );
+        let all_time = store
+            .get_price_summary(product_id, dealer_id, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(all_time.samples, 4);
+        assert_eq!(all_time.percent_change, Some(10.0));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{Result, StoreError};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A product field a search term can be narrowed down to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryField {
+    Product,
+    Brand,
+    Item,
+    Pack,
+}
+
+impl QueryField {
+    fn from_name(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "product" | "name" => Ok(QueryField::Product),
+            "brand" => Ok(QueryField::Brand),
+            "item" => Ok(QueryField::Item),
+            "pack" => Ok(QueryField::Pack),
+            _ => Err(StoreError::InvalidQuery(format!(
+                "There is no \"{name}:\" to search by. Try brand:, item:, product: or pack:"
+            ))),
+        }
+    }
+}
+
+/// One term of a `ProductQuery`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryTerm {
+    /// `None` when the term may match any field.
+    pub field: Option<QueryField>,
+    /// Lowercase. A quoted phrase keeps its spaces.
+    pub text: String,
+    /// The term rules products out instead of in.
+    pub negated: bool,
+}
+
+/// A product search as typed into a search box, e.g.
+/// `brand:amul pack:500g ghee` or `item:oil -brand:fortune`.
+///
+/// Terms are separated by spaces and each can be
+/// - a word, matched against every field and ranked as usual,
+/// - a `"quoted phrase"`, matched the same way but as a whole,
+/// - `field:word` or `field:"quoted phrase"`, which the product's brand,
+///   item, product name or pack has to match,
+/// - any of those after a `-`, leaving out the products containing it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProductQuery {
+    pub terms: Vec<QueryTerm>,
+}
+
+impl ProductQuery {
+    /// Parses `input`, explaining what to fix when it cannot.
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut terms = Vec::new();
+        let mut chars = input.chars().peekable();
+
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+
+            let negated = chars.next_if_eq(&'-').is_some();
+            let (field, text) = if chars.peek() == Some(&'"') {
+                (None, phrase(&mut chars)?)
+            } else {
+                let word = word(&mut chars);
+                match word.split_once(':') {
+                    Some((name, value))
+                        if !name.is_empty() && name.chars().all(char::is_alphabetic) =>
+                    {
+                        let field = QueryField::from_name(name)?;
+                        let value = if value.is_empty() && chars.peek() == Some(&'"') {
+                            phrase(&mut chars)?
+                        } else {
+                            value.to_string()
+                        };
+                        if value.is_empty() {
+                            return Err(StoreError::InvalidQuery(format!(
+                                "Type what to search by after \"{name}:\", as in {name}:amul"
+                            )));
+                        }
+                        (Some(field), value)
+                    }
+                    _ => (None, word),
+                }
+            };
+
+            if text.is_empty() {
+                return Err(StoreError::InvalidQuery(if negated {
+                    "Type what to leave out right after the \"-\", as in -brand:fortune".to_string()
+                } else {
+                    "The quotes are empty. Type a phrase between them".to_string()
+                }));
+            }
+            terms.push(QueryTerm {
+                field,
+                text: text.to_lowercase(),
+                negated,
+            });
+        }
+
+        Ok(ProductQuery { terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The plain terms, which are ranked against every field.
+    pub(crate) fn ranked(&self) -> impl Iterator<Item = &str> {
+        self.terms
+            .iter()
+            .filter(|term| term.field.is_none() && !term.negated)
+            .map(|term| term.text.as_str())
+    }
+
+    /// Every word a matching product has to contain something like, for
+    /// looking up candidates.
+    pub(crate) fn wanted_words(&self) -> Vec<&str> {
+        self.terms
+            .iter()
+            .filter(|term| !term.negated)
+            .flat_map(|term| term.text.split_whitespace())
+            .collect()
+    }
+}
+
+/// Reads up to the next space or quote.
+fn word(chars: &mut Peekable<Chars>) -> String {
+    let mut word = String::new();
+    while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '"') {
+        word.push(c);
+    }
+    word
+}
+
+/// Reads a quoted phrase, opening quote included, and returns what was
+/// between the quotes.
+fn phrase(chars: &mut Peekable<Chars>) -> Result<String> {
+    chars.next();
+    let mut phrase = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            return Ok(phrase.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+        phrase.push(c);
+    }
+    Err(StoreError::InvalidQuery(format!(
+        "Close the quote after \"{}",
+        phrase.trim_end()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(field: Option<QueryField>, text: &str, negated: bool) -> QueryTerm {
+        QueryTerm {
+            field,
+            text: text.to_string(),
+            negated,
+        }
+    }
+
+    #[test]
+    fn qualifiers_negation_and_phrases_are_parsed() {
+        assert_eq!(
+            ProductQuery::parse("Brand:Amul pack:500g  ghee")
+                .unwrap()
+                .terms,
+            vec![
+                term(Some(QueryField::Brand), "amul", false),
+                term(Some(QueryField::Pack), "500g", false),
+                term(None, "ghee", false),
+            ]
+        );
+        assert_eq!(
+            ProductQuery::parse("item:oil -brand:fortune -\"rice bran\"")
+                .unwrap()
+                .terms,
+            vec![
+                term(Some(QueryField::Item), "oil", false),
+                term(Some(QueryField::Brand), "fortune", true),
+                term(None, "rice bran", true),
+            ]
+        );
+        assert_eq!(
+            ProductQuery::parse("brand:\"Mother  Dairy\" coca-cola 1:2")
+                .unwrap()
+                .terms,
+            vec![
+                term(Some(QueryField::Brand), "mother dairy", false),
+                term(None, "coca-cola", false),
+                term(None, "1:2", false),
+            ]
+        );
+        assert!(ProductQuery::parse("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn mistakes_are_explained() {
+        for (input, message) in [
+            ("colour:red", "There is no \"colour:\" to search by"),
+            ("brand: amul", "Type what to search by after \"brand:\""),
+            ("ghee -", "Type what to leave out"),
+            ("\"sunflower oil", "Close the quote after \"sunflower oil"),
+            ("\"\"", "The quotes are empty"),
+        ] {
+            match ProductQuery::parse(input) {
+                Err(StoreError::InvalidQuery(error)) => {
+                    assert!(error.starts_with(message), "{input}: {error}")
+                }
+                other => panic!("{input}: {other:?}"),
+            }
+        }
+    }
+}
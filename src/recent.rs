@@ -0,0 +1,202 @@
+use crate::{boost, product_from_row, Product, Result, Store, StoreError, PRODUCT_QUERY};
+use rusqlite::{params, Connection};
+
+/// The search boxes that remember what was picked from them. Each keeps its
+/// own history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchContext {
+    Comparison,
+    DataFeed,
+}
+
+impl SearchContext {
+    fn as_str(self) -> &'static str {
+        match self {
+            SearchContext::Comparison => "comparison",
+            SearchContext::DataFeed => "data_feed",
+        }
+    }
+}
+
+const RECENT_LIMIT_SETTING: &str = "recent_product_limit";
+pub const DEFAULT_RECENT_LIMIT: u32 = 5;
+
+impl Store {
+    /// Remembers that the product was picked in `context`. Picking it again
+    /// moves it back to the front instead of listing it twice. Every pick
+    /// also lifts the product in later searches; see `SelectionWeighting`.
+    pub fn record_product_selection(
+        &mut self,
+        context: SearchContext,
+        product_id: i64,
+    ) -> Result<()> {
+        self.record_product_selection_at(context, product_id, "now")
+    }
+
+    /// Records a pick as of `selected_at`, which SQLite's `datetime()` must
+    /// understand, e.g. "2024-03-01 09:30:00" or "now".
+    pub fn record_product_selection_at(
+        &mut self,
+        context: SearchContext,
+        product_id: i64,
+        selected_at: &str,
+    ) -> Result<()> {
+        self.ensure_exists("product", product_id)?;
+        let readable: bool = self.connection.query_row(
+            "SELECT datetime(?1) IS NOT NULL",
+            params![selected_at],
+            |row| row.get(0),
+        )?;
+        if !readable {
+            return Err(StoreError::validation(format!(
+                "\"{selected_at}\" is not a date and time"
+            )));
+        }
+        let limit = self.recent_product_limit()?;
+        let half_life_days = self.selection_weighting()?.half_life_days;
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "
+            INSERT INTO recent_product (context, product_id, recency)
+            VALUES (?1, ?2, (SELECT IFNULL(MAX(recency), 0) + 1 FROM recent_product WHERE context = ?1))
+            ON CONFLICT (context, product_id) DO UPDATE SET recency = excluded.recency",
+            params![context.as_str(), product_id],
+        )?;
+        trim_recent_products(&transaction, context, limit)?;
+        boost::count_selection(&transaction, product_id, selected_at, half_life_days)?;
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// The products last picked in `context`, most recent first.
+    pub fn get_recent_product_results(&self, context: SearchContext) -> Result<Vec<Product>> {
+        let products = self
+            .connection
+            .prepare(&format!(
+                "{PRODUCT_QUERY}
+                JOIN recent_product rp ON rp.product_id = product.product_id
+                WHERE rp.context = ?1 AND product.archived_at IS NULL
+                ORDER BY rp.recency DESC"
+            ))?
+            .query_map(params![context.as_str()], product_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(products)
+    }
+
+    pub fn clear_recent_products(&mut self, context: SearchContext) -> Result<()> {
+        self.connection.execute(
+            "DELETE FROM recent_product WHERE context = ?1",
+            params![context.as_str()],
+        )?;
+        Ok(())
+    }
+
+    /// How many recent products each context keeps.
+    pub fn recent_product_limit(&self) -> Result<u32> {
+        Ok(self
+            .setting(RECENT_LIMIT_SETTING)?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_RECENT_LIMIT))
+    }
+
+    /// Changes the cap, dropping the oldest entries beyond it straight away.
+    pub fn set_recent_product_limit(&mut self, limit: u32) -> Result<()> {
+        if limit == 0 {
+            return Err(StoreError::validation(
+                "At least one recent product must be kept",
+            ));
+        }
+
+        self.set_setting(RECENT_LIMIT_SETTING, &limit.to_string())?;
+        for context in [SearchContext::Comparison, SearchContext::DataFeed] {
+            trim_recent_products(&self.connection, context, limit)?;
+        }
+        Ok(())
+    }
+}
+
+fn trim_recent_products(connection: &Connection, context: SearchContext, limit: u32) -> Result<()> {
+    connection.execute(
+        "
+        DELETE FROM recent_product
+        WHERE context = ?1
+        AND product_id NOT IN (
+            SELECT product_id FROM recent_product WHERE context = ?1 ORDER BY recency DESC LIMIT ?2
+        )",
+        params![context.as_str(), limit],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn names(products: &[Product]) -> Vec<&str> {
+        products
+            .iter()
+            .map(|product| product.pack_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn recent_products_are_deduplicated_and_capped() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let packs = ["100g", "200g", "500g", "1kg"];
+        let ids: Vec<i64> = packs
+            .iter()
+            .map(|pack| {
+                store
+                    .add_product("Butter", "Amul", "Butter", pack, None)
+                    .unwrap()
+            })
+            .collect();
+        store.set_recent_product_limit(3).unwrap();
+
+        for id in [ids[0], ids[1], ids[0], ids[2], ids[3]] {
+            store
+                .record_product_selection(SearchContext::Comparison, id)
+                .unwrap();
+        }
+        store
+            .record_product_selection(SearchContext::DataFeed, ids[1])
+            .unwrap();
+
+        let comparison = store
+            .get_recent_product_results(SearchContext::Comparison)
+            .unwrap();
+        assert_eq!(names(&comparison), vec!["1kg", "500g", "100g"]);
+        let data_feed = store
+            .get_recent_product_results(SearchContext::DataFeed)
+            .unwrap();
+        assert_eq!(names(&data_feed), vec!["200g"]);
+
+        store.set_recent_product_limit(1).unwrap();
+        assert_eq!(
+            store
+                .get_recent_product_results(SearchContext::Comparison)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        store
+            .clear_recent_products(SearchContext::Comparison)
+            .unwrap();
+        assert!(store
+            .get_recent_product_results(SearchContext::Comparison)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            store
+                .get_recent_product_results(SearchContext::DataFeed)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
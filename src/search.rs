@@ -0,0 +1,748 @@
+use crate::alias::{load_alias_names, AliasNames};
+use crate::{
+    address, product_from_row, Dealer, Product, ProductQuery, QueryField, Result, Store,
+    PRODUCT_QUERY,
+};
+use levenshtein::levenshtein;
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+use std::iter;
+
+/// How many of the search index's best hits the search dropdowns score.
+/// Scoring is what costs, so this bounds a keystroke however large the
+/// catalog is. The Products page lists every match instead.
+const CANDIDATE_LIMIT: usize = 200;
+
+/// How the Products page orders its list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProductSort {
+    /// Best match first while there is search text, by name otherwise.
+    #[default]
+    Relevance,
+    Name,
+    Brand,
+    /// Most recently priced first. Products nobody has priced come last.
+    RecentlyPriced,
+}
+
+/// What the Products page list is narrowed down to. The default lists every
+/// active product by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProductFilter {
+    pub text: String,
+    pub brand_id: Option<i64>,
+    pub item_id: Option<i64>,
+    /// Also matches the categories below this one.
+    pub category_id: Option<i64>,
+    /// Lists archived products instead of active ones.
+    pub archived: bool,
+    /// Only the products whose pack name could not be read as a size.
+    pub unparsed_packs: bool,
+    pub sort: ProductSort,
+}
+
+impl Store {
+    /// The five products best matching `new_text`, for the search dropdowns.
+    /// Products picked from them before rank higher, as `SelectionWeighting`
+    /// describes. `new_text` follows the `ProductQuery` syntax and a mistake in it is an
+    /// `InvalidQuery` error.
+    pub fn get_best_product_results_for(&self, new_text: &str) -> Result<Vec<Product>> {
+        let query = ProductQuery::parse(new_text)?;
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let candidates =
+            self.filtered_products(&ProductFilter::default(), &query, Some(CANDIDATE_LIMIT))?;
+        let aliases = load_alias_names(&self.connection)?;
+        let mut results = rank_products(candidates, &query, &aliases);
+        let boosts = self.selection_boosts()?;
+        for (product, score) in &mut results {
+            *score += boosts.get(&product.product_id).copied().unwrap_or(0);
+        }
+        results.sort_by_key(|(_, score)| Reverse(*score));
+        results.truncate(5);
+
+        // Return only the products
+        Ok(results.into_iter().map(|(product, _)| product).collect())
+    }
+
+    /// Every product passing `filter`, in the order it asks for. The text
+    /// follows the `ProductQuery` syntax.
+    pub fn search_products(&self, filter: &ProductFilter) -> Result<Vec<Product>> {
+        let query = ProductQuery::parse(&filter.text)?;
+        let products = self.filtered_products(filter, &query, None)?;
+        let mut results = if query.is_empty() {
+            products.into_iter().map(|product| (product, 0)).collect()
+        } else {
+            rank_products(products, &query, &load_alias_names(&self.connection)?)
+        };
+
+        match filter.sort {
+            ProductSort::Relevance => results.sort_by(|(a, a_score), (b, b_score)| {
+                b_score.cmp(a_score).then_with(|| by_name(a, b))
+            }),
+            ProductSort::Name => results.sort_by(|(a, _), (b, _)| by_name(a, b)),
+            ProductSort::Brand => results.sort_by(|(a, _), (b, _)| {
+                a.brand_name
+                    .to_lowercase()
+                    .cmp(&b.brand_name.to_lowercase())
+                    .then_with(|| by_name(a, b))
+            }),
+            ProductSort::RecentlyPriced => {
+                let priced_at = self.last_priced_at()?;
+                // `None` sorts before any time, so comparing b to a puts the
+                // products nobody has priced last.
+                results.sort_by(|(a, _), (b, _)| {
+                    priced_at
+                        .get(&b.product_id)
+                        .cmp(&priced_at.get(&a.product_id))
+                        .then_with(|| by_name(a, b))
+                })
+            }
+        }
+
+        Ok(results.into_iter().map(|(product, _)| product).collect())
+    }
+
+    /// Every brand as `(brand_id, name)`, alphabetically.
+    pub fn get_brands(&self) -> Result<Vec<(i64, String)>> {
+        self.query_names("SELECT brand_id, name FROM brand ORDER BY name COLLATE NOCASE")
+    }
+
+    /// Every item as `(item_id, name)`, alphabetically.
+    pub fn get_items(&self) -> Result<Vec<(i64, String)>> {
+        self.query_names("SELECT item_id, name FROM item ORDER BY name COLLATE NOCASE, item_id")
+    }
+
+    /// Active dealers matching `new_text` on their name, city or phone
+    /// number, best first. Part of a number, such as its last four digits,
+    /// is enough to find the dealer.
+    pub fn get_best_dealer_results_for(&self, new_text: &str) -> Result<Vec<Dealer>> {
+        let query = new_text.trim().to_lowercase();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Only a query that looks like a number is matched against numbers,
+        // so that typing "2" does not bring up every dealer.
+        let digits: String = query.chars().filter(char::is_ascii_digit).collect();
+        let is_number = digits.len() >= 3
+            && query
+                .chars()
+                .all(|c| c.is_ascii_digit() || " +-()".contains(c));
+
+        let cities = address::load_dealer_cities(&self.connection)?;
+
+        let mut results: Vec<(Dealer, usize)> = self
+            .get_dealers()?
+            .into_iter()
+            .map(|dealer| {
+                let first_name = dealer.first_name.to_lowercase();
+                let last_name = dealer.last_name.to_lowercase();
+                let mut fields = vec![format!("{first_name} {last_name}"), first_name, last_name];
+                if let Some(middle_name) = &dealer.middle_name {
+                    fields.push(middle_name.to_lowercase());
+                }
+                if let Some(cities) = cities.get(&dealer.dealer_id) {
+                    fields.extend(cities.iter().map(|city| city.to_lowercase()));
+                }
+                let mut score = match_score(&fields, &query);
+
+                if is_number {
+                    for contact in &dealer.contacts {
+                        let e164 = contact.e164();
+                        for number in [contact.phone_number.as_str(), e164.trim_start_matches('+')]
+                        {
+                            let number_score = if number == digits {
+                                1000
+                            } else if number.ends_with(&digits) || number.starts_with(&digits) {
+                                500
+                            } else if number.contains(&digits) {
+                                200
+                            } else {
+                                0
+                            };
+                            score = score.max(number_score);
+                        }
+                    }
+                }
+
+                (dealer, score)
+            })
+            .filter(|(_, score)| *score > 0)
+            .collect();
+
+        results.sort_by_key(|(_, score)| Reverse(*score));
+        Ok(results.into_iter().map(|(dealer, _)| dealer).collect())
+    }
+
+    /// Products passing `filter`'s archived, category, brand, item and pack
+    /// filters. Given a `query`, the search index's candidates for the words
+    /// it wants are loaded, best first and at most `limit` of them, and
+    /// `rank_products` has the final say.
+    ///
+    /// A product sharing any three-letter run with one of those words is a
+    /// candidate, so near misses like "britania" still find "Britannia".
+    /// When every word is too short for that, each word has to appear
+    /// somewhere in the fields instead, with prefixes of the first word first.
+    fn filtered_products(
+        &self,
+        filter: &ProductFilter,
+        query: &ProductQuery,
+        limit: Option<usize>,
+    ) -> Result<Vec<Product>> {
+        let mut conditions = vec![if filter.archived {
+            "product.archived_at IS NOT NULL".to_string()
+        } else {
+            "product.archived_at IS NULL".to_string()
+        }];
+        if let Some(category_id) = filter.category_id {
+            let category_ids: Vec<String> = self
+                .get_category_subtree(category_id)?
+                .iter()
+                .map(|category| category.category_id.to_string())
+                .collect();
+            conditions.push(format!("item.category_id IN ({})", category_ids.join(", ")));
+        }
+        if let Some(brand_id) = filter.brand_id {
+            conditions.push(format!("product.brand_id = {brand_id}"));
+        }
+        if let Some(item_id) = filter.item_id {
+            conditions.push(format!("product.item_id = {item_id}"));
+        }
+        if filter.unparsed_packs {
+            conditions.push("product.pack_unit IS NULL".to_string());
+        }
+
+        let words = query.wanted_words();
+        let trigrams = trigram_query(&words);
+        let mut search = "";
+        let mut order = String::new();
+        let mut patterns = Vec::new();
+        if !words.is_empty() {
+            search = "JOIN product_search ON product_search.rowid = product.product_id";
+        }
+        if !trigrams.is_empty() {
+            conditions.push("product_search MATCH ?1".to_string());
+            order = "ORDER BY product_search.rank".to_string();
+            patterns.push(trigrams);
+        } else if !words.is_empty() {
+            let any_field = |parameter: usize| {
+                [
+                    "product_name",
+                    "brand_name",
+                    "item_name",
+                    "pack_name",
+                    "aliases",
+                ]
+                .map(|field| format!("product_search.{field} LIKE ?{parameter} ESCAPE '\\'"))
+                .join(" OR ")
+            };
+            let escape = |word: &str| {
+                word.replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_")
+            };
+            for word in &words {
+                patterns.push(format!("%{}%", escape(word)));
+                conditions.push(format!("({})", any_field(patterns.len())));
+            }
+            patterns.push(format!("{}%", escape(words[0])));
+            order = format!(
+                "ORDER BY ({}) DESC, product.product_id",
+                any_field(patterns.len())
+            );
+        }
+
+        if let (Some(limit), false) = (limit, words.is_empty()) {
+            order.push_str(&format!(" LIMIT {limit}"));
+        }
+
+        let products = self
+            .connection
+            .prepare(&format!(
+                "{PRODUCT_QUERY} {search} WHERE {} {order}",
+                conditions.join(" AND ")
+            ))?
+            .query_map(rusqlite::params_from_iter(patterns), product_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(products)
+    }
+
+    /// When each product was last priced by anyone.
+    fn last_priced_at(&self) -> Result<HashMap<i64, String>> {
+        let priced_at = self
+            .connection
+            .prepare("SELECT product_id, MAX(time_stamp) FROM dealer_price GROUP BY product_id")?
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(priced_at)
+    }
+
+    fn query_names(&self, sql: &str) -> Result<Vec<(i64, String)>> {
+        let names = self
+            .connection
+            .prepare(sql)?
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(names)
+    }
+}
+
+/// Scores every product against `query` and drops the ones that do not
+/// match it.
+///
+/// Each term of the query is scored on its own against the product, brand,
+/// item and pack names, their `aliases` and the words in them, so "amul butter 500g" can
+/// match three different fields in any order. Every term has to match its
+/// field, or any field when it names none, and their scores add up. Plain
+/// words that together match a whole name, like "sunflower oil", score that
+/// on top. A negated term drops the products containing it.
+fn rank_products(
+    products: Vec<Product>,
+    query: &ProductQuery,
+    aliases: &AliasNames,
+) -> Vec<(Product, usize)> {
+    let ranked: Vec<&str> = query.ranked().collect();
+    let phrase = ranked.join(" ");
+    let aliases_of = |names: &HashMap<i64, Vec<String>>, id: i64| -> Vec<String> {
+        names.get(&id).cloned().unwrap_or_default()
+    };
+
+    products
+        .into_iter()
+        .filter_map(|product| {
+            // Every name each field goes by, the real one first.
+            let fields: [Vec<String>; 4] = [
+                iter::once(product.product_name.to_lowercase())
+                    .chain(aliases_of(&aliases.products, product.product_id))
+                    .collect(),
+                iter::once(product.brand_name.to_lowercase())
+                    .chain(aliases_of(&aliases.brands, product.brand_id))
+                    .collect(),
+                iter::once(product.item_name.to_lowercase())
+                    .chain(aliases_of(&aliases.items, product.item_id))
+                    .collect(),
+                vec![product.pack_name.to_lowercase()],
+            ];
+
+            let mut score = 0;
+            for term in &query.terms {
+                let searched = match term.field {
+                    None => fields.concat(),
+                    Some(QueryField::Product) => fields[0].clone(),
+                    Some(QueryField::Brand) => fields[1].clone(),
+                    Some(QueryField::Item) => fields[2].clone(),
+                    Some(QueryField::Pack) => fields[3].clone(),
+                };
+                if term.negated {
+                    if searched.iter().any(|field| field.contains(&term.text)) {
+                        return None;
+                    }
+                    continue;
+                }
+
+                let mut field_words: Vec<String> = searched
+                    .iter()
+                    .flat_map(|field| field.split_whitespace())
+                    .map(str::to_string)
+                    .collect();
+                field_words.extend(searched);
+                match match_score(&field_words, &term.text) {
+                    0 => return None,
+                    term_score => score += term_score,
+                }
+            }
+            if ranked.len() > 1 {
+                score += match_score(&fields.concat(), &phrase);
+            }
+            Some((product, score))
+        })
+        .collect()
+}
+
+/// An FTS5 query matching any three-letter run of any of `words`. Empty when
+/// every word is shorter than that.
+fn trigram_query(words: &[&str]) -> String {
+    let mut trigrams: Vec<String> = words
+        .iter()
+        .flat_map(|word| {
+            let chars: Vec<char> = word.chars().collect();
+            chars
+                .windows(3)
+                .map(|window| window.iter().collect::<String>().replace('"', "\"\""))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    trigrams.sort();
+    trigrams.dedup();
+    trigrams
+        .iter()
+        .map(|trigram| format!("\"{trigram}\""))
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
+/// Orders by product name, then brand, then pack, ignoring case.
+fn by_name(a: &Product, b: &Product) -> Ordering {
+    let key = |product: &Product| {
+        (
+            product.product_name.to_lowercase(),
+            product.brand_name.to_lowercase(),
+            product.pack_name.to_lowercase(),
+        )
+    };
+    key(a).cmp(&key(b))
+}
+
+/// Scores `query` against the best of `fields`, all lowercase. An exact match
+/// beats a prefix, a prefix beats a substring and a substring beats a near
+/// miss of up to two edits.
+fn match_score(fields: &[String], query: &str) -> usize {
+    let mut score = 0;
+
+    if fields.iter().any(|field| field == query) {
+        score += 1000;
+    }
+
+    if fields.iter().any(|field| field.starts_with(query)) {
+        score += 500;
+    }
+
+    if fields.iter().any(|field| field.contains(query)) {
+        score += 200;
+    }
+
+    let min_distance = fields
+        .iter()
+        .map(|field| levenshtein(field, query))
+        .min()
+        .unwrap_or(usize::MAX);
+
+    if min_distance <= 2 {
+        score += 100 - min_distance * 30;
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Address, Contact, Money, StoreError};
+    use std::path::Path;
+
+    #[test]
+    fn dealers_are_found_by_name_city_and_number() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let number = |phone_number: &str| Contact {
+            country_code: "+91".to_string(),
+            phone_number: phone_number.to_string(),
+            ..Default::default()
+        };
+        let ramesh = store
+            .add_dealer("Ramesh", None, "Gupta", &[number("9876543210")])
+            .unwrap();
+        let suresh = store
+            .add_dealer("Suresh", Some("Kumar"), "Jain", &[number("9812345678")])
+            .unwrap();
+        store
+            .add_dealer_address(
+                suresh,
+                &Address {
+                    city_name: "Indore".to_string(),
+                    district_name: "Indore".to_string(),
+                    pin_code: "452001".to_string(),
+                    state: "Madhya Pradesh".to_string(),
+                    country: "India".to_string(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let found = |text: &str| -> Vec<i64> {
+            store
+                .get_best_dealer_results_for(text)
+                .unwrap()
+                .iter()
+                .map(|dealer| dealer.dealer_id)
+                .collect()
+        };
+        assert_eq!(found("ramesh gupta"), vec![ramesh]);
+        assert_eq!(found("Rmesh"), vec![ramesh]);
+        assert_eq!(found("kumar"), vec![suresh]);
+        assert_eq!(found("indore"), vec![suresh]);
+        assert_eq!(found("3210"), vec![ramesh]);
+        assert_eq!(found("+91 98"), vec![ramesh, suresh]);
+        assert!(found("").is_empty());
+        assert!(found("21").is_empty());
+    }
+
+    #[test]
+    fn dealers_with_unparsed_numbers_can_be_searched() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let mohan = store.add_dealer("Mohan", None, "Lal", &[]).unwrap();
+        // Left as typed by the phone number migration, which could not read it.
+        store
+            .connection
+            .execute_batch(&format!(
+                "INSERT INTO phone (phone_id, country_code, phone_number) VALUES (1, '＋९१', '९८७६५ 43210');
+                 INSERT INTO dealer_contact (dealer_id, phone_id, is_primary) VALUES ({mohan}, 1, 1);"
+            ))
+            .unwrap();
+
+        let found: Vec<i64> = store
+            .get_best_dealer_results_for("43210")
+            .unwrap()
+            .iter()
+            .map(|dealer| dealer.dealer_id)
+            .collect();
+        assert_eq!(found, vec![mohan]);
+    }
+
+    #[test]
+    fn products_page_filters_and_sorts() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let dairy = store.add_category("Dairy", None).unwrap();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", Some(dairy))
+            .unwrap();
+        let cheese = store
+            .add_product("Cheese Slices", "Britannia", "Cheese", "200g", Some(dairy))
+            .unwrap();
+        let ghee = store
+            .add_product("Ghee", "Amul", "Ghee", "1L", None)
+            .unwrap();
+        let ramesh = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+        store
+            .update_price_at(
+                butter,
+                ramesh,
+                Money::from_rupees(250),
+                "2024-01-01 10:00:00",
+            )
+            .unwrap();
+        store
+            .update_price_at(ghee, ramesh, Money::from_rupees(600), "2024-02-01 10:00:00")
+            .unwrap();
+
+        let ids = |filter: ProductFilter| -> Vec<i64> {
+            store
+                .search_products(&filter)
+                .unwrap()
+                .iter()
+                .map(|product| product.product_id)
+                .collect()
+        };
+        let amul = store.get_product(butter).unwrap().brand_id;
+
+        assert_eq!(ids(ProductFilter::default()), vec![butter, cheese, ghee]);
+        assert_eq!(
+            ids(ProductFilter {
+                category_id: Some(dairy),
+                sort: ProductSort::Brand,
+                ..Default::default()
+            }),
+            vec![butter, cheese]
+        );
+        assert_eq!(
+            ids(ProductFilter {
+                brand_id: Some(amul),
+                sort: ProductSort::RecentlyPriced,
+                ..Default::default()
+            }),
+            vec![ghee, butter]
+        );
+        assert_eq!(
+            ids(ProductFilter {
+                sort: ProductSort::RecentlyPriced,
+                ..Default::default()
+            }),
+            vec![ghee, butter, cheese]
+        );
+        assert_eq!(
+            ids(ProductFilter {
+                text: "britania".to_string(),
+                ..Default::default()
+            }),
+            vec![cheese]
+        );
+
+        let brands: Vec<_> = store
+            .get_brands()
+            .unwrap()
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect();
+        assert_eq!(brands, vec!["Amul", "Britannia"]);
+    }
+
+    #[test]
+    fn products_page_lists_every_match() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        for n in 0..CANDIDATE_LIMIT + 10 {
+            store
+                .add_product(&format!("Biscuit {n}"), "Parle", "Biscuit", "100g", None)
+                .unwrap();
+        }
+
+        let found = |text: &str| {
+            store
+                .search_products(&ProductFilter {
+                    text: text.to_string(),
+                    ..Default::default()
+                })
+                .unwrap()
+                .len()
+        };
+        assert_eq!(found("biscuit"), CANDIDATE_LIMIT + 10);
+        assert_eq!(found("bi"), CANDIDATE_LIMIT + 10);
+        assert_eq!(
+            store.get_best_product_results_for("biscuit").unwrap().len(),
+            5
+        );
+    }
+
+    #[test]
+    fn search_index_follows_edits_and_deletes() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let oil = store
+            .add_product("Sunflower Oil", "Fortune", "Oil", "1L", None)
+            .unwrap();
+
+        let best = |store: &Store, text: &str| -> Vec<i64> {
+            store
+                .get_best_product_results_for(text)
+                .unwrap()
+                .iter()
+                .map(|product| product.product_id)
+                .collect()
+        };
+        assert_eq!(best(&store, "btter"), vec![butter]);
+        assert_eq!(best(&store, "am"), vec![butter]);
+        assert_eq!(best(&store, "1l"), vec![oil]);
+        assert!(best(&store, "50%").is_empty());
+
+        store
+            .update_product(
+                butter,
+                "Table Butter",
+                "Mother Dairy",
+                "Butter",
+                "500g",
+                None,
+            )
+            .unwrap();
+        assert!(best(&store, "amul").is_empty());
+        assert_eq!(best(&store, "mother"), vec![butter]);
+
+        let fortune = store.get_product(oil).unwrap().brand_id;
+        store.delete_brand(fortune).unwrap();
+        assert!(best(&store, "sunflower").is_empty());
+        let indexed: i64 = store
+            .connection
+            .query_row("SELECT COUNT(*) FROM product_search", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(indexed, 1);
+    }
+
+    #[test]
+    fn words_match_any_field_in_any_order() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let amul_500 = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let amul_100 = store
+            .add_product("Butter", "Amul", "Butter", "100g", None)
+            .unwrap();
+        let britannia = store
+            .add_product("Butter", "Britannia", "Butter", "500g", None)
+            .unwrap();
+        let salt = store
+            .add_product("Iodised Salt", "Tata", "Salt", "1kg", None)
+            .unwrap();
+        let oil = store
+            .add_product("Sunflower Oil", "Fortune", "Oil", "1L", None)
+            .unwrap();
+
+        let best = |text: &str| -> Vec<i64> {
+            store
+                .get_best_product_results_for(text)
+                .unwrap()
+                .iter()
+                .map(|product| product.product_id)
+                .collect()
+        };
+        assert_eq!(best("amul butter 500g")[0], amul_500);
+        assert_eq!(best("500g butter amul")[0], amul_500);
+        assert_eq!(best("amul butter 100g")[0], amul_100);
+        // Ties come back in either order, so only the top two are compared.
+        let top_two = |text: &str| {
+            let mut ids = best(text)[..2].to_vec();
+            ids.sort();
+            ids
+        };
+        assert_eq!(top_two("butter 500g"), [amul_500, britannia]);
+        assert_eq!(top_two("amool buter"), [amul_500, amul_100]);
+        assert_eq!(best("tata salt"), vec![salt]);
+        assert_eq!(best("salt iodised 1kg"), vec![salt]);
+        assert_eq!(best("sunflower oil 1l"), vec![oil]);
+        assert!(best("amul salt").is_empty());
+    }
+
+    #[test]
+    fn qualified_and_negated_terms_narrow_results() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let amul_ghee = store
+            .add_product("Pure Ghee", "Amul", "Ghee", "500g", None)
+            .unwrap();
+        store
+            .add_product("Pure Ghee", "Amul", "Ghee", "1L", None)
+            .unwrap();
+        let patanjali_ghee = store
+            .add_product("Cow Ghee", "Patanjali", "Ghee", "500g", None)
+            .unwrap();
+        let sunflower = store
+            .add_product("Sunflower Oil", "Fortune", "Oil", "1L", None)
+            .unwrap();
+        let rice_bran = store
+            .add_product("Rice Bran Oil", "Saffola", "Oil", "1L", None)
+            .unwrap();
+        let mustard = store
+            .add_product("Mustard Oil", "Mother Dairy", "Oil", "1L", None)
+            .unwrap();
+
+        let best = |text: &str| -> Vec<i64> {
+            let mut ids: Vec<i64> = store
+                .get_best_product_results_for(text)
+                .unwrap()
+                .iter()
+                .map(|product| product.product_id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(best("brand:amul pack:500g ghee"), vec![amul_ghee]);
+        assert_eq!(best("pack:500g ghee"), vec![amul_ghee, patanjali_ghee]);
+        assert_eq!(best("item:oil -brand:fortune"), vec![rice_bran, mustard]);
+        assert_eq!(best("oil -\"rice bran\""), vec![sunflower, mustard]);
+        assert_eq!(best("brand:\"mother dairy\""), vec![mustard]);
+        assert!(best("brand:fortune ghee").is_empty());
+
+        assert!(matches!(
+            store.get_best_product_results_for("colour:red"),
+            Err(StoreError::InvalidQuery(_))
+        ));
+        assert!(matches!(
+            store.search_products(&ProductFilter {
+                text: "\"ghee".to_string(),
+                ..Default::default()
+            }),
+            Err(StoreError::InvalidQuery(_))
+        ));
+    }
+}
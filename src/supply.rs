@@ -0,0 +1,135 @@
+use crate::{
+    contact, dealer_from_row, product_from_row, Dealer, Money, Product, Result, Store, StoreError,
+    PRODUCT_QUERY,
+};
+use rusqlite::params;
+
+impl Store {
+    /// Records that the dealer stocks the product. Linking twice is harmless.
+    pub fn link_dealer_product(&mut self, dealer_id: i64, product_id: i64) -> Result<()> {
+        self.ensure_exists("dealer", dealer_id)?;
+        self.ensure_exists("product", product_id)?;
+
+        self.connection.execute(
+            "INSERT OR IGNORE INTO dealer_product (dealer_id, product_id) VALUES (?1, ?2)",
+            params![dealer_id, product_id],
+        )?;
+        Ok(())
+    }
+
+    /// The dealer no longer stocks the product. Their price history is kept.
+    pub fn unlink_dealer_product(&mut self, dealer_id: i64, product_id: i64) -> Result<()> {
+        let removed = self.connection.execute(
+            "DELETE FROM dealer_product WHERE dealer_id = ?1 AND product_id = ?2",
+            params![dealer_id, product_id],
+        )?;
+
+        if removed == 0 {
+            return Err(StoreError::NotFound("dealer product link"));
+        }
+        Ok(())
+    }
+
+    /// Active dealers who stock the product.
+    pub fn get_product_dealers(&self, product_id: i64) -> Result<Vec<Dealer>> {
+        Ok(self
+            .get_dealer_price_pairs_for(product_id)?
+            .into_iter()
+            .map(|(dealer, _)| dealer)
+            .collect())
+    }
+
+    /// Products the dealer stocks, leaving out archived ones.
+    pub fn get_dealer_products(&self, dealer_id: i64) -> Result<Vec<Product>> {
+        self.ensure_exists("dealer", dealer_id)?;
+
+        let products = self
+            .connection
+            .prepare(&format!(
+                "{PRODUCT_QUERY}
+                JOIN dealer_product dp ON dp.product_id = product.product_id
+                WHERE dp.dealer_id = ?1 AND product.archived_at IS NULL
+                ORDER BY product.product_id"
+            ))?
+            .query_map(params![dealer_id], product_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(products)
+    }
+
+    /// Every active dealer who stocks the product with their latest price, or
+    /// `None` for those who have not quoted one yet.
+    pub fn get_dealer_price_pairs_for(
+        &self,
+        product_id: i64,
+    ) -> Result<Vec<(Dealer, Option<Money>)>> {
+        self.ensure_exists("product", product_id)?;
+
+        let mut pairs = self
+            .connection
+            .prepare(
+                "
+                SELECT d.dealer_id, d.first_name, d.middle_name, d.last_name, d.archived_at IS NOT NULL, (
+                    SELECT price_paise FROM dealer_price dp
+                    WHERE dp.dealer_id = d.dealer_id AND dp.product_id = link.product_id
+                    ORDER BY dp.time_stamp DESC LIMIT 1
+                )
+                FROM dealer_product link
+                JOIN dealer d ON d.dealer_id = link.dealer_id
+                WHERE link.product_id = ?1 AND d.archived_at IS NULL
+                ORDER BY d.dealer_id
+                ",
+            )?
+            .query_map(params![product_id], |row| {
+                Ok((dealer_from_row(row)?, row.get(5)?))
+            })?
+            .collect::<rusqlite::Result<Vec<(Dealer, Option<Money>)>>>()?;
+
+        for (dealer, _) in &mut pairs {
+            dealer.contacts = contact::load_contacts(&self.connection, dealer.dealer_id)?;
+        }
+        Ok(pairs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn linked_dealers_show_up_without_a_price() {
+        let mut store = Store::build(Path::new(":memory:")).unwrap();
+        let butter = store
+            .add_product("Butter", "Amul", "Butter", "500g", None)
+            .unwrap();
+        let ramesh = store.add_dealer("Ramesh", None, "Gupta", &[]).unwrap();
+        let suresh = store.add_dealer("Suresh", None, "Jain", &[]).unwrap();
+
+        assert!(store.get_dealer_price_pairs_for(butter).unwrap().is_empty());
+
+        store.link_dealer_product(ramesh, butter).unwrap();
+        store.link_dealer_product(ramesh, butter).unwrap();
+        store
+            .update_price(butter, suresh, Money::from_rupees(250))
+            .unwrap();
+
+        let pairs = store.get_dealer_price_pairs_for(butter).unwrap();
+        let prices: Vec<_> = pairs
+            .iter()
+            .map(|(dealer, price)| (dealer.dealer_id, *price))
+            .collect();
+        assert_eq!(
+            prices,
+            vec![(ramesh, None), (suresh, Some(Money::from_rupees(250)))]
+        );
+        assert_eq!(store.get_dealer_products(suresh).unwrap().len(), 1);
+
+        store.unlink_dealer_product(suresh, butter).unwrap();
+        assert_eq!(store.get_product_dealers(butter).unwrap().len(), 1);
+        assert_eq!(store.get_price_history(butter, suresh).unwrap().len(), 1);
+        assert!(matches!(
+            store.unlink_dealer_product(suresh, butter),
+            Err(StoreError::NotFound(_))
+        ));
+    }
+}